@@ -1,41 +1,77 @@
-use crate::actions::{copy_to_clipboard, unlock, unlock_totp_vault, Action, ItemType};
-use crate::vault::entities::Error;
+use crate::actions::{
+    copy_or_print, password_generator_from_args, report_ambiguous_entropy, unlock,
+    unlock_totp_vault, Action, ItemType,
+};
+use crate::crypto::PasswordGenerator;
+use crate::vault::entities::{Credential, Error};
 use crate::vault::vault_trait::Vault;
-use crate::{crypto, ui};
+use crate::{clipboard, config, crypto, store, ui};
 use clap::ArgMatches;
-use clipboard::{ClipboardContext, ClipboardProvider};
 
 pub struct AddAction {
     pub generate: bool,
     pub clipboard: bool,
     pub item_type: ItemType,
     pub is_totp: bool,
+    pub no_username: bool,
+    pub no_auto_tags: bool,
+    pub force: bool,
+    pub password_generator: PasswordGenerator,
+    pub service: Option<String>,
+    pub username: Option<String>,
+    pub password: Option<String>,
+    pub notes: Option<String>,
+    pub tags: Vec<String>,
 }
 
 impl AddAction {
     pub fn new(matches: &ArgMatches) -> AddAction {
         AddAction {
-            generate: matches.get_one::<bool>("generate").map_or(false, |v| *v),
-            clipboard: matches.get_one::<bool>("clipboard").map_or(false, |v| *v),
+            generate: matches.get_flag("generate"),
+            clipboard: matches.get_flag("clipboard"),
             item_type: ItemType::new_from_args(matches),
-            is_totp: matches.get_one::<bool>("otp").map_or(false, |v| *v),
+            is_totp: matches.get_flag("otp"),
+            no_username: matches.get_flag("no-username"),
+            no_auto_tags: matches.get_flag("no-auto-tags"),
+            force: matches.get_flag("force"),
+            password_generator: password_generator_from_args(matches),
+            service: matches.get_one::<String>("service").cloned(),
+            username: matches.get_one::<String>("username").cloned(),
+            password: matches.get_one::<String>("password").cloned(),
+            notes: matches.get_one::<String>("with-notes").cloned(),
+            tags: matches
+                .get_many::<String>("tag")
+                .map_or(vec![], |tags| tags.cloned().collect()),
         }
     }
-    fn password_from_clipboard(&self) -> Result<String, Error> {
-        let mut ctx: ClipboardContext = ClipboardProvider::new().unwrap();
-        let value = ctx
-            .get_contents()
-            .expect("Unable to retrieve value from clipboard");
-        if !crypto::validate_password(&value) {
-            Error::new("The text in clipboard is not a valid password");
-        }
+    // The passwords taken from the clipboard have to pass the password policy
+    fn validate_password(value: String, source: &str) -> Result<String, Error> {
+        crypto::validate_password(&value, &config::get().password_policy).map_err(|reason| {
+            Error::new(&format!(
+                "The {} is not a valid password: {}",
+                source, reason
+            ))
+        })?;
         Ok(value)
     }
+    fn password_from_clipboard(&self) -> Result<String, Error> {
+        Self::validate_password(clipboard::get_contents()?, "text in clipboard")
+    }
+    // A password piped in, e.g. from `passlane generate`. It's saved as it is like a typed one. When
+    // the vault is not unlocked, the master password is read from the next line of stdin.
+    fn password_from_stdin(value: Option<String>) -> Result<String, Error> {
+        value.ok_or_else(|| Error::new("No password was piped to stdin"))
+    }
     fn get_password(&self) -> Result<String, Error> {
-        if self.generate {
-            Ok(crypto::generate())
+        if let Some(password) = &self.password {
+            Ok(password.clone())
+        } else if self.generate {
+            report_ambiguous_entropy(&self.password_generator);
+            self.password_generator.generate()
         } else if self.clipboard {
             self.password_from_clipboard()
+        } else if ui::input::stdin_is_piped() {
+            Self::password_from_stdin(ui::input::read_piped_password())
         } else {
             Ok(ui::input::ask_password("Enter password to save", None))
         }
@@ -47,14 +83,67 @@ impl AddAction {
             unlock()
         }
     }
+    fn usernames_for_service(vault: &dyn Vault, service: &str) -> Vec<String> {
+        let mut usernames: Vec<String> = vault
+            .grep(Some(service))
+            .iter()
+            .filter(|c| c.service().to_lowercase().contains(&service.to_lowercase()))
+            .map(|c| c.username().to_string())
+            .collect();
+        usernames.sort();
+        usernames.dedup();
+        usernames
+    }
     fn add_credential(&self) -> Result<String, Error> {
+        let service = match &self.service {
+            Some(service) => Some(
+                ui::input::validate_service(service, self.force)
+                    .map_err(|e| Error::new(&format!("{}, use --force to save it anyway", e)))?,
+            ),
+            None => None,
+        };
         let password = self.get_password()?;
 
-        let creds = ui::input::ask_credentials(&password);
         let mut vault = self.get_vault()?;
-        vault.save_one_credential(creds.clone())?;
-        copy_to_clipboard(&password);
-        Ok("Password copied to clipboard".to_string())
+        let creds = ui::input::ask_credentials(
+            &password,
+            service,
+            self.username.clone(),
+            self.no_username,
+            self.force,
+            |service| Self::usernames_for_service(vault.as_ref(), service),
+        );
+        let auto_tags = if self.no_auto_tags {
+            vec![]
+        } else {
+            store::tags_for_service(creds.service())?
+        };
+        let mut tags = self.tags.clone();
+        for tag in &auto_tags {
+            if !tags.iter().any(|t| t.eq_ignore_ascii_case(tag)) {
+                tags.push(tag.clone());
+            }
+        }
+        let creds = Credential::new(
+            None,
+            creds.password(),
+            creds.service(),
+            creds.username(),
+            None,
+            &tags,
+            self.notes.as_deref(),
+        )
+        .with_no_username(!creds.has_username());
+        vault.save_one_credential(creds)?;
+        let mut message = if copy_or_print(&password) {
+            "Password copied to clipboard".to_string()
+        } else {
+            "Credentials saved".to_string()
+        };
+        if !auto_tags.is_empty() {
+            message.push_str(&format!(". Auto-applied tags: {}", auto_tags.join(", ")));
+        }
+        Ok(message)
     }
     fn add_payment(&self) -> Result<String, Error> {
         let payment = ui::input::ask_payment_info();
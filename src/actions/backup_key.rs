@@ -0,0 +1,72 @@
+use crate::actions::Action;
+use crate::store;
+use crate::ui::input::ask_new_backup_passphrase;
+use crate::vault::entities::{Error, Note};
+use crate::vault::keepass_vault::KeepassVault;
+use crate::vault::vault_trait::NoteVault;
+use clap::ArgMatches;
+use std::fs;
+use std::path::Path;
+use zeroize::Zeroizing;
+
+// The title of the secure note that holds the hex encoded key file in the backup
+pub(crate) const KEY_BACKUP_NOTE_TITLE: &str = "Passlane key file";
+
+/// Path of the key file of the vault, or of the One Time Passwords vault
+pub(crate) fn configured_keyfile_path(is_totp: bool) -> Result<Option<String>, Error> {
+    if is_totp {
+        store::get_totp_keyfile_path()
+    } else {
+        store::get_keyfile_path()
+    }
+}
+
+/// Writes the key to a new Keepass file at the path, hex encoded in a secure note
+pub(crate) fn write_key_backup(file_path: &str, key: &[u8], passphrase: &str) -> Result<(), Error> {
+    let key = Zeroizing::new(hex::encode(key));
+    let mut backup = KeepassVault::new(file_path, passphrase, None)?;
+    backup.save_note(&Note::new(None, KEY_BACKUP_NOTE_TITLE, &key, None))
+}
+
+/// Writes the key file to a new Keepass file protected by a passphrase of its own,
+/// so that the key is never written anywhere unprotected.
+pub struct BackupKeyAction {
+    pub file_path: String,
+    pub is_totp: bool,
+    pub force: bool,
+}
+
+impl BackupKeyAction {
+    pub fn new(matches: &ArgMatches) -> BackupKeyAction {
+        BackupKeyAction {
+            file_path: matches
+                .get_one::<String>("FILE_PATH")
+                .expect("required")
+                .to_string(),
+            is_totp: matches.get_flag("otp"),
+            force: matches.get_flag("force"),
+        }
+    }
+}
+
+impl Action for BackupKeyAction {
+    fn run(&self) -> Result<String, Error> {
+        let keyfile = configured_keyfile_path(self.is_totp)?
+            .ok_or_else(|| Error::new("The vault has no key file, there is nothing to back up"))?;
+        if !self.force && Path::new(&self.file_path).exists() {
+            return Err(Error::new(&format!(
+                "File '{}' already exists, use --force to overwrite it",
+                self.file_path
+            )));
+        }
+        let key = Zeroizing::new(fs::read(&keyfile).map_err(|e| {
+            Error::new(&format!("Failed to read the key file '{}': {}", keyfile, e))
+        })?);
+        let passphrase = Zeroizing::new(ask_new_backup_passphrase());
+        write_key_backup(&self.file_path, &key, &passphrase)?;
+        Ok(format!(
+            "Key file '{}' backed up to '{}'. Keep the passphrase safe, the backup can't be restored without it",
+            keyfile, self.file_path
+        ))
+    }
+}
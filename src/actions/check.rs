@@ -0,0 +1,192 @@
+use crate::actions::UnlockingAction;
+use crate::crypto;
+use crate::ui::output::{show_breached_table, show_credentials_table};
+use crate::vault::entities::{Credential, Error};
+use crate::vault::vault_trait::Vault;
+use clap::ArgMatches;
+use sha1::{Digest, Sha1};
+use std::collections::HashMap;
+use std::env;
+use std::io::{self, IsTerminal, Write};
+use std::process::Command;
+use std::thread::sleep;
+use std::time::Duration;
+
+const RANGE_API_URL: &str = "https://api.pwnedpasswords.com/range";
+// Overrides the address of the range API, mainly for testing
+const RANGE_API_URL_ENV: &str = "PASSLANE_HIBP_URL";
+// Only this many characters of the hash are sent, the rest is matched locally
+const HASH_PREFIX_LENGTH: usize = 5;
+// Pause between the requests to be polite to the API
+const REQUEST_INTERVAL: Duration = Duration::from_millis(200);
+
+// The uppercase hex SHA-1 of the password split to the prefix that is sent and the suffix that isn't
+fn hash_parts(password: &str) -> (String, String) {
+    let hash = hex::encode_upper(Sha1::digest(password.as_bytes()));
+    let (prefix, suffix) = hash.split_at(HASH_PREFIX_LENGTH);
+    (prefix.to_string(), suffix.to_string())
+}
+
+// Asks the range API for the hash suffixes that start with the prefix, using curl for the HTTPS request
+fn fetch_range(prefix: &str) -> Result<String, Error> {
+    let base_url = env::var(RANGE_API_URL_ENV).unwrap_or(RANGE_API_URL.to_string());
+    let url = format!("{}/{}", base_url.trim_end_matches('/'), prefix);
+    let output = Command::new("curl")
+        .args(["--silent", "--show-error", "--fail"])
+        .args(["--user-agent", "passlane"])
+        // padding hides the number of suffixes in the response from eavesdroppers
+        .args(["--header", "Add-Padding: true"])
+        .arg(&url)
+        .output()
+        .map_err(|e| Error::new(&format!("Failed to run curl: {}", e)))?;
+    if !output.status.success() {
+        return Err(Error::new(&format!(
+            "Failed to query {}: {}",
+            url,
+            String::from_utf8_lossy(&output.stderr).trim()
+        )));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).to_string())
+}
+
+// The number of breaches of the suffix in a response of `SUFFIX:COUNT` lines. Padding lines have a zero count.
+fn breach_count(range: &str, suffix: &str) -> u64 {
+    range
+        .lines()
+        .filter_map(|line| line.trim().split_once(':'))
+        .find(|(candidate, _)| candidate.eq_ignore_ascii_case(suffix))
+        .and_then(|(_, count)| count.trim().parse().ok())
+        .unwrap_or(0)
+}
+
+// Shows how many passwords have been checked on one line that's rewritten, only when stderr is a terminal
+struct Progress {
+    total: usize,
+    enabled: bool,
+}
+
+impl Progress {
+    fn new(total: usize) -> Progress {
+        Progress {
+            total,
+            enabled: io::stderr().is_terminal(),
+        }
+    }
+
+    fn show(&self, checked: usize) {
+        if self.enabled {
+            eprint!("\rChecked {}/{} passwords", checked, self.total);
+            let _ = io::stderr().flush();
+        }
+    }
+}
+
+// Clears the progress line when the check ends, also on errors, so that the results are printed on an empty line
+impl Drop for Progress {
+    fn drop(&mut self) {
+        if self.enabled {
+            eprint!("\r\x1b[2K");
+            let _ = io::stderr().flush();
+        }
+    }
+}
+
+fn find_breached(credentials: Vec<Credential>) -> Result<Vec<(Credential, u64)>, Error> {
+    let mut ranges: HashMap<String, String> = HashMap::new();
+    let mut breached = Vec::new();
+    let progress = Progress::new(credentials.len());
+    for (index, credential) in credentials.into_iter().enumerate() {
+        progress.show(index);
+        let (prefix, suffix) = hash_parts(credential.password());
+        if !ranges.contains_key(&prefix) {
+            if !ranges.is_empty() {
+                sleep(REQUEST_INTERVAL);
+            }
+            ranges.insert(prefix.clone(), fetch_range(&prefix)?);
+        }
+        let count = breach_count(&ranges[&prefix], &suffix);
+        if count > 0 {
+            breached.push((credential, count));
+        }
+    }
+    Ok(breached)
+}
+
+/// Checks the passwords against the breached passwords of Have I Been Pwned.
+/// Only the first characters of the SHA-1 hash of a password leave this machine.
+pub struct CheckAction {
+    pub offline: bool,
+}
+
+impl CheckAction {
+    pub fn new(matches: &ArgMatches) -> CheckAction {
+        CheckAction {
+            offline: matches.get_flag("offline"),
+        }
+    }
+}
+
+impl CheckAction {
+    // Without the network only the built-in list of common passwords can be checked
+    fn check_offline(credentials: Vec<Credential>) -> String {
+        let total = credentials.len();
+        let common: Vec<Credential> = credentials
+            .into_iter()
+            .filter(|c| crypto::is_common(c.password()))
+            .collect();
+        if common.is_empty() {
+            return format!(
+                "None of the {} passwords are common passwords. Have I Been Pwned was not queried because of --offline",
+                total
+            );
+        }
+        show_credentials_table(&common, false);
+        format!(
+            "{} of the {} passwords are common passwords, change them with `passlane edit`. Have I Been Pwned was not queried because of --offline",
+            common.len(),
+            total
+        )
+    }
+}
+
+impl UnlockingAction for CheckAction {
+    fn run_with_vault(&self, vault: &mut Box<dyn Vault>) -> Result<Option<String>, Error> {
+        let credentials = vault.grep(None);
+        if self.offline {
+            return Ok(Some(Self::check_offline(credentials)));
+        }
+        let total = credentials.len();
+        eprintln!("Checking {} passwords against Have I Been Pwned...", total);
+        let breached = find_breached(credentials)?;
+        if breached.is_empty() {
+            return Ok(Some(format!(
+                "None of the {} passwords were found in breaches",
+                total
+            )));
+        }
+        show_breached_table(&breached);
+        Ok(Some(format!(
+            "{} of the {} passwords were found in breaches, change them with `passlane edit`",
+            breached.len(),
+            total
+        )))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::mixed_vault;
+
+    #[test]
+    fn offline_check_finds_the_common_passwords_without_notes_or_cards() {
+        let action = CheckAction { offline: true };
+        let mut vault: Box<dyn Vault> = Box::new(mixed_vault("check-offline"));
+        let result = action.run_with_vault(&mut vault).unwrap().unwrap();
+        assert!(
+            result.starts_with("1 of the 1 passwords are common passwords"),
+            "{}",
+            result
+        );
+    }
+}
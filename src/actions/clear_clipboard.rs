@@ -0,0 +1,30 @@
+use crate::actions::{restore_clipboard_after, Action, PendingClear};
+use crate::clipboard;
+use crate::vault::entities::Error;
+use clap::ArgMatches;
+use std::io;
+
+pub struct ClearClipboardAction {
+    /// Set in the helper process that clears a copied value later, it reads the value from stdin
+    pub after: Option<u64>,
+}
+
+impl ClearClipboardAction {
+    pub fn new(matches: &ArgMatches) -> ClearClipboardAction {
+        ClearClipboardAction {
+            after: matches.get_one::<u64>("after").copied(),
+        }
+    }
+}
+
+impl Action for ClearClipboardAction {
+    fn run(&self) -> Result<String, Error> {
+        if let Some(seconds) = self.after {
+            let pending: PendingClear = serde_json::from_reader(io::stdin())?;
+            restore_clipboard_after(seconds, pending)?;
+            return Ok(String::new());
+        }
+        clipboard::set("")?;
+        Ok("Clipboard cleared".to_string())
+    }
+}
@@ -0,0 +1,29 @@
+use crate::actions::Action;
+use crate::vault::entities::Error;
+use clap::{ArgMatches, Command};
+use clap_complete::{generate, Shell};
+
+pub struct CompletionsAction {
+    cli: Command,
+    shell: Shell,
+}
+
+impl CompletionsAction {
+    pub fn new(cli: Command, matches: &ArgMatches) -> CompletionsAction {
+        CompletionsAction {
+            cli,
+            shell: *matches.get_one::<Shell>("SHELL").expect("required"),
+        }
+    }
+}
+
+impl Action for CompletionsAction {
+    fn run(&self) -> Result<String, Error> {
+        let mut script = Vec::new();
+        let mut cli = self.cli.clone();
+        let name = cli.get_name().to_string();
+        generate(self.shell, &mut cli, name, &mut script);
+        String::from_utf8(script)
+            .map_err(|_| Error::new("Failed to convert the completion script to string"))
+    }
+}
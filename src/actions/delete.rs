@@ -1,4 +1,6 @@
-use crate::actions::{handle_matches, ItemType, MatchHandlerTemplate, UnlockingAction};
+use crate::actions::{
+    handle_matches, no_matches, ItemType, MatchHandlerTemplate, SearchPattern, UnlockingAction,
+};
 use crate::ui;
 use crate::ui::output::{
     show_credentials_table, show_notes_table, show_payment_cards_table, show_totp_table,
@@ -9,7 +11,7 @@ use clap::ArgMatches;
 
 struct DeleteCredentialsTemplate<'a> {
     vault: &'a mut Box<dyn Vault>,
-    grep: &'a str,
+    pattern: &'a SearchPattern,
 }
 
 impl<'a> MatchHandlerTemplate for DeleteCredentialsTemplate<'a> {
@@ -29,14 +31,19 @@ impl<'a> MatchHandlerTemplate for DeleteCredentialsTemplate<'a> {
         matches: Vec<Self::ItemType>,
     ) -> Result<Option<String>, Error> {
         show_credentials_table(&matches, false);
-        match ui::input::ask_index(
-            "To delete, please enter a row number from the table above",
-            matches.len() as i16 - 1,
-            Some("Press q to exit without deleting"),
-        ) {
+        let index = if ui::input::can_pick(matches.len()) {
+            ui::input::pick_credential("Select the credential to delete", &matches)
+        } else {
+            ui::input::ask_index(
+                "To delete, please enter a row number from the table above",
+                matches.len() as i16 - 1,
+                Some("Press q to exit without deleting"),
+            )
+        };
+        match index {
             Ok(index) => {
                 if index == usize::MAX {
-                    self.vault.delete_matching(self.grep)?;
+                    self.pattern.delete_credentials(self.vault)?;
                     Ok(Some("Deleted".to_string()))
                 } else {
                     println!(
@@ -195,6 +202,10 @@ pub struct DeleteAction {
     pub grep: Option<String>,
     pub item_type: ItemType,
     pub is_totp: bool,
+    pub dry_run: bool,
+    pub all: bool,
+    pub yes: bool,
+    pub regex: bool,
 }
 
 impl DeleteAction {
@@ -203,7 +214,76 @@ impl DeleteAction {
             grep: matches.get_one::<String>("REGEXP").cloned(),
             item_type: ItemType::new_from_args(matches),
             is_totp: matches.get_one::<bool>("otp").map_or(false, |v| *v),
+            dry_run: matches.get_flag("dry-run"),
+            all: matches.get_flag("all"),
+            yes: matches.get_flag("yes"),
+            regex: matches.get_flag("regex"),
+        }
+    }
+
+    fn credentials_pattern(&self) -> Result<SearchPattern, Error> {
+        match &self.grep {
+            Some(grep) => SearchPattern::new(grep, self.regex),
+            None => Err(Error {
+                message: "No search term provided".to_string(),
+            }),
+        }
+    }
+
+    // Lists the matching entries without deleting anything
+    fn dry_run(&self, vault: &dyn Vault) -> Result<Option<String>, Error> {
+        let count = match self.item_type {
+            ItemType::Credential => {
+                let matches = self.credentials_pattern()?.find_credentials(vault);
+                show_credentials_table(&matches, false);
+                matches.len()
+            }
+            ItemType::Payment => {
+                let matches = vault.find_payments();
+                show_payment_cards_table(&matches, false);
+                matches.len()
+            }
+            ItemType::Note => {
+                let matches = vault.find_notes();
+                show_notes_table(&matches, false);
+                matches.len()
+            }
+            ItemType::Totp => {
+                let matches = vault.find_totp(self.grep.as_deref());
+                show_totp_table(&matches);
+                matches.len()
+            }
+        };
+        Ok(Some(format!(
+            "Dry run: {} entries would be deleted, nothing was deleted",
+            count
+        )))
+    }
+}
+
+impl DeleteAction {
+    // Deletes all matching credentials without asking for a row, confirming first unless --yes is given
+    fn delete_all(&self, vault: &mut Box<dyn Vault>) -> Result<Option<String>, Error> {
+        let pattern = self.credentials_pattern()?;
+        let matches = pattern.find_credentials(vault.as_ref());
+        if matches.is_empty() {
+            return no_matches();
+        }
+        show_credentials_table(&matches, false);
+        if !self.yes {
+            let answer = ui::input::ask_with_options(
+                &format!(
+                    "Do you want to delete these {} credentials? (yes/no)",
+                    matches.len()
+                ),
+                vec!["yes", "no"],
+            );
+            if answer != "yes" {
+                return Ok(None);
+            }
         }
+        let count = pattern.delete_credentials(vault)?;
+        Ok(Some(format!("Deleted {} credentials", count)))
     }
 }
 
@@ -213,19 +293,21 @@ impl UnlockingAction for DeleteAction {
     }
 
     fn run_with_vault(&self, vault: &mut Box<dyn Vault>) -> Result<Option<String>, Error> {
+        if self.dry_run {
+            return self.dry_run(vault.as_ref());
+        }
+        if self.all {
+            return self.delete_all(vault);
+        }
         match self.item_type {
             ItemType::Credential => {
-                let grep = match &self.grep {
-                    Some(grep) => grep.as_str(),
-                    None => {
-                        return Err(Error {
-                            message: "No search term provided".to_string(),
-                        })
-                    }
-                };
+                let pattern = self.credentials_pattern()?;
                 handle_matches(
-                    vault.grep(Some(grep)),
-                    &mut Box::new(DeleteCredentialsTemplate { vault, grep }),
+                    pattern.find_credentials(vault.as_ref()),
+                    &mut Box::new(DeleteCredentialsTemplate {
+                        vault,
+                        pattern: &pattern,
+                    }),
                 )
             }
             ItemType::Payment => handle_matches(
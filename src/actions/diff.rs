@@ -0,0 +1,79 @@
+use crate::actions::{json_pretty_from_args, UnlockingAction};
+use crate::store;
+use crate::ui::output::{show_diff_table, to_json};
+use crate::vault::entities::{Credential, CredentialDiff, DiffStatus, Error};
+use crate::vault::vault_trait::Vault;
+use clap::ArgMatches;
+use std::collections::HashMap;
+
+pub struct DiffAction {
+    pub file_path: String,
+    pub json: bool,
+    pub pretty: Option<bool>,
+}
+
+impl DiffAction {
+    pub fn new(matches: &ArgMatches) -> DiffAction {
+        DiffAction {
+            file_path: matches
+                .get_one::<String>("FILE_PATH")
+                .expect("required")
+                .to_string(),
+            json: matches.get_flag("json"),
+            pretty: json_pretty_from_args(matches),
+        }
+    }
+}
+
+fn by_service_and_username(creds: &[Credential]) -> HashMap<(&str, &str), &Credential> {
+    creds
+        .iter()
+        .map(|c| ((c.service(), c.username()), c))
+        .collect()
+}
+
+fn diff_credentials(vault_creds: &[Credential], file_creds: &[Credential]) -> Vec<CredentialDiff> {
+    let in_vault = by_service_and_username(vault_creds);
+    let in_file = by_service_and_username(file_creds);
+    let diff = |(service, username): &(&str, &str), status| CredentialDiff {
+        service: service.to_string(),
+        username: username.to_string(),
+        status,
+    };
+
+    let mut diffs: Vec<CredentialDiff> = Vec::new();
+    for (key, cred) in &in_vault {
+        match in_file.get(key) {
+            None => diffs.push(diff(key, DiffStatus::OnlyInVault)),
+            Some(other) if other.password() != cred.password() => {
+                diffs.push(diff(key, DiffStatus::PasswordDiffers))
+            }
+            _ => {}
+        }
+    }
+    for key in in_file.keys() {
+        if !in_vault.contains_key(key) {
+            diffs.push(diff(key, DiffStatus::OnlyInFile));
+        }
+    }
+    diffs.sort_by(|a, b| (&a.service, &a.username).cmp(&(&b.service, &b.username)));
+    diffs
+}
+
+impl UnlockingAction for DiffAction {
+    fn run_with_vault(&self, vault: &mut Box<dyn Vault>) -> Result<Option<String>, Error> {
+        let file_creds = store::read_from_csv(&self.file_path, b',')?;
+        let diffs = diff_credentials(&vault.grep(None), &file_creds);
+        if self.json {
+            return Ok(Some(to_json(&diffs, self.pretty)?));
+        }
+        if diffs.is_empty() {
+            return Ok(Some(format!(
+                "The vault and '{}' have the same credentials",
+                self.file_path
+            )));
+        }
+        show_diff_table(&diffs);
+        Ok(Some(format!("Found {} differences", diffs.len())))
+    }
+}
@@ -1,5 +1,6 @@
 use clap::ArgMatches;
 
+use crate::actions::SearchPattern;
 use crate::ui::output::{
     show_credentials_table, show_notes_table, show_payment_cards_table, show_totp_table,
 };
@@ -9,6 +10,7 @@ use crate::{handle_matches, ui, ItemType, MatchHandlerTemplate, UnlockingAction}
 
 struct EditCredentialsTemplate<'a> {
     vault: &'a mut Box<dyn Vault>,
+    tags: Option<&'a [String]>,
 }
 
 impl<'a> EditCredentialsTemplate<'a> {
@@ -16,7 +18,7 @@ impl<'a> EditCredentialsTemplate<'a> {
         &mut self,
         credential: &Credential,
     ) -> Result<Option<String>, Error> {
-        let updated = ui::input::ask_modified_credential(credential);
+        let updated = ui::input::ask_modified_credential(credential, self.tags);
         println!("Saving...");
         self.vault.update_credential(updated)?;
         Ok(Some("Saved".to_string()))
@@ -204,6 +206,8 @@ pub struct EditAction {
     pub grep: Option<String>,
     pub item_type: ItemType,
     pub is_totp: bool,
+    pub tags: Option<Vec<String>>,
+    pub regex: bool,
 }
 
 impl EditAction {
@@ -212,6 +216,10 @@ impl EditAction {
             grep: matches.get_one::<String>("REGEXP").cloned(),
             item_type: ItemType::new_from_args(matches),
             is_totp: matches.get_one::<bool>("otp").map_or(false, |v| *v),
+            tags: matches
+                .get_many::<String>("tag")
+                .map(|tags| tags.cloned().collect()),
+            regex: matches.get_flag("regex"),
         }
     }
 }
@@ -233,8 +241,11 @@ impl UnlockingAction for EditAction {
                     }
                 };
                 handle_matches(
-                    vault.grep(Some(grep)),
-                    &mut Box::new(EditCredentialsTemplate { vault }),
+                    SearchPattern::new(grep, self.regex)?.find_credentials(vault.as_ref()),
+                    &mut Box::new(EditCredentialsTemplate {
+                        vault,
+                        tags: self.tags.as_deref(),
+                    }),
                 )
             }
             ItemType::Payment => handle_matches(
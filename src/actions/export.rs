@@ -1,13 +1,19 @@
 use clap::ArgMatches;
 use log::debug;
+use std::path::Path;
 use crate::actions::{ItemType, UnlockingAction};
-use crate::store;
-use crate::vault::entities::Error;
+use crate::{crypto, store};
+use crate::vault::entities::{Credential, Error};
 use crate::vault::vault_trait::Vault;
 
 pub struct ExportAction {
     pub file_path: String,
     pub item_type: ItemType,
+    pub recovery_codes: bool,
+    pub only_weak: bool,
+    pub only_reused: bool,
+    pub format: String,
+    pub force: bool,
 }
 
 impl ExportAction {
@@ -15,16 +21,44 @@ impl ExportAction {
         ExportAction {
             file_path: matches.get_one::<String>("file_path").expect("required").to_string(),
             item_type: ItemType::new_from_args(matches),
+            recovery_codes: matches.get_flag("recovery"),
+            only_weak: matches.get_flag("only-weak"),
+            only_reused: matches.get_flag("only-reused"),
+            format: matches
+                .get_one::<String>("format")
+                .cloned()
+                .unwrap_or("csv".to_string()),
+            force: matches.get_flag("force"),
         }
     }
+    // with both filters the credentials that are either weak or reused are kept
+    fn filter_for_remediation(&self, creds: Vec<Credential>) -> Vec<Credential> {
+        if !self.only_weak && !self.only_reused {
+            return creds;
+        }
+        let reused = crypto::find_reused(&creds);
+        creds
+            .into_iter()
+            .filter(|c| {
+                (self.only_weak && crypto::is_weak(c.password()))
+                    || (self.only_reused && reused.iter().any(|r| r.uuid() == c.uuid()))
+            })
+            .collect()
+    }
     pub fn export_csv(&self, vault: &mut Box<dyn Vault>) -> Result<i64, Error> {
         debug!("exporting to csv");
-        if self.item_type == ItemType::Credential {
-            let creds = vault.grep(None);
+        if self.recovery_codes {
+            let recovery_codes = vault.find_recovery_codes(None);
+            store::write_recovery_codes_to_csv(&self.file_path, &recovery_codes)
+        } else if self.item_type == ItemType::Credential {
+            let creds = self.filter_for_remediation(vault.grep(None));
             if creds.is_empty() {
                 println!("No credentials found");
                 return Ok(0);
             }
+            if self.format == "json" {
+                return store::write_credentials_to_json(&self.file_path, &creds);
+            }
             store::write_credentials_to_csv(&self.file_path, &creds)
         } else if self.item_type == ItemType::Payment {
             let cards = vault.find_payments();
@@ -40,6 +74,40 @@ impl ExportAction {
 
 impl UnlockingAction for ExportAction {
     fn run_with_vault(&self, vault: &mut Box<dyn Vault>) -> Result<Option<String>, Error> {
+        if !self.force && Path::new(&self.file_path).exists() {
+            return Err(Error::new(&format!(
+                "File '{}' already exists, use --force to overwrite it",
+                self.file_path
+            )));
+        }
+        if self.format == "json" && (self.recovery_codes || self.item_type != ItemType::Credential) {
+            return Err(Error::new("Only credentials can be exported as JSON"));
+        }
         self.export_csv(vault).map(|count| format!("Exported {} entries", count)).map(Some)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::{mixed_vault, test_dir};
+    use std::fs;
+
+    #[test]
+    fn notes_and_cards_are_not_exported_as_credentials() {
+        for format in ["csv", "json"] {
+            let path = test_dir(&format!("export-mixed-{}", format)).join("export");
+            let path = path.to_string_lossy().to_string();
+            let matches =
+                crate::cli().get_matches_from(["passlane", "export", "--format", format, &path]);
+            let action = ExportAction::new(matches.subcommand_matches("export").unwrap());
+            let mut vault: Box<dyn Vault> =
+                Box::new(mixed_vault(&format!("export-vault-{}", format)));
+            assert_eq!(action.export_csv(&mut vault).unwrap(), 1);
+            let exported = fs::read_to_string(&path).unwrap();
+            assert!(exported.contains("real.com"));
+            assert!(!exported.contains("secret note body"), "{}", exported);
+            assert!(!exported.contains("4111111111111111"), "{}", exported);
+        }
+    }
+}
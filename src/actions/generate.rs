@@ -1,13 +1,45 @@
-use crate::actions::{copy_to_clipboard, Action};
-use crate::crypto;
+use crate::actions::{
+    copy_or_print, password_generator_from_args, report_ambiguous_entropy, Action,
+};
+use crate::crypto::PasswordGenerator;
+use crate::store;
 use crate::vault::entities::Error;
+use clap::ArgMatches;
+use std::io::{self, IsTerminal};
 
-pub struct GeneratePasswordAction;
+pub struct GeneratePasswordAction {
+    pub out: Option<String>,
+    pub force: bool,
+    pub no_copy: bool,
+    pub password_generator: PasswordGenerator,
+}
+
+impl GeneratePasswordAction {
+    pub fn new(matches: &ArgMatches) -> GeneratePasswordAction {
+        GeneratePasswordAction {
+            out: matches.get_one::<String>("out").cloned(),
+            force: matches.get_flag("force"),
+            no_copy: matches.get_flag("no-copy"),
+            password_generator: password_generator_from_args(matches),
+        }
+    }
+}
 
 impl Action for GeneratePasswordAction {
     fn run(&self) -> Result<String, Error> {
-        let password = crypto::generate();
-        copy_to_clipboard(&password);
+        let password = self.password_generator.generate()?;
+        report_ambiguous_entropy(&self.password_generator);
+        if let Some(out) = &self.out {
+            store::write_private_file(out, &password, self.force)?;
+            return Ok(format!("Password written to '{}'", out));
+        }
+        // only the password is printed when it's piped, e.g. to `passlane add`
+        if self.no_copy || !io::stdout().is_terminal() {
+            return Ok(password);
+        }
+        if !copy_or_print(&password) {
+            return Ok(String::new());
+        }
         Ok("Password - also copied to clipboard".to_string())
     }
 }
@@ -0,0 +1,101 @@
+use crate::actions::{
+    copy_or_print, handle_matches, MatchHandlerTemplate, SearchPattern, UnlockingAction,
+};
+use crate::ui::input::ask_index;
+use crate::ui::output::show_credentials_table;
+use crate::vault::entities::{Credential, Error};
+use crate::vault::vault_trait::Vault;
+use clap::ArgMatches;
+
+struct PasswordHistoryTemplate<'a> {
+    vault: &'a dyn Vault,
+    verbose: bool,
+}
+
+impl<'a> PasswordHistoryTemplate<'a> {
+    fn show_history(&self, credential: &Credential) -> Result<Option<String>, Error> {
+        let versions = self.vault.password_history(credential.uuid());
+        if versions.is_empty() {
+            return Ok(Some(format!(
+                "No earlier passwords for '{}'",
+                credential.service()
+            )));
+        }
+        println!(
+            "Earlier passwords of '{}' ({}), the newest first:",
+            credential.service(),
+            credential.username()
+        );
+        show_credentials_table(&versions, self.verbose);
+        match ask_index(
+            "To copy one of these passwords to clipboard, enter a row number from the table above",
+            versions.len() as i16 - 1,
+            Some("Press q to exit without copying a password"),
+        ) {
+            Ok(index) if copy_or_print(versions[index].password()) => {
+                Ok(Some("Password copied to clipboard!".to_string()))
+            }
+            Ok(_) => Ok(None),
+            Err(message) => Err(Error { message }),
+        }
+    }
+}
+
+impl<'a> MatchHandlerTemplate for PasswordHistoryTemplate<'a> {
+    type ItemType = Credential;
+
+    fn pre_handle_matches(&self, matches: &Vec<Self::ItemType>) {
+        println!("Found {} credentials:", matches.len());
+    }
+
+    fn handle_one_match(&mut self, the_match: Self::ItemType) -> Result<Option<String>, Error> {
+        self.show_history(&the_match)
+    }
+
+    fn handle_many_matches(
+        &mut self,
+        matches: Vec<Self::ItemType>,
+    ) -> Result<Option<String>, Error> {
+        show_credentials_table(&matches, false);
+        match ask_index(
+            "To see the earlier passwords, enter a row number from the table above",
+            matches.len() as i16 - 1,
+            Some("Press q to exit"),
+        ) {
+            Ok(index) => self.show_history(&matches[index]),
+            Err(message) => Err(Error { message }),
+        }
+    }
+}
+
+pub struct HistoryAction {
+    pub grep: String,
+    pub verbose: bool,
+    pub regex: bool,
+}
+
+impl HistoryAction {
+    pub fn new(matches: &ArgMatches) -> HistoryAction {
+        HistoryAction {
+            grep: matches
+                .get_one::<String>("REGEXP")
+                .expect("required")
+                .to_string(),
+            verbose: matches.get_flag("verbose"),
+            regex: matches.get_flag("regex"),
+        }
+    }
+}
+
+impl UnlockingAction for HistoryAction {
+    fn run_with_vault(&self, vault: &mut Box<dyn Vault>) -> Result<Option<String>, Error> {
+        let matches = SearchPattern::new(&self.grep, self.regex)?.find_credentials(vault.as_ref());
+        handle_matches(
+            matches,
+            &mut Box::new(PasswordHistoryTemplate {
+                vault: vault.as_ref(),
+                verbose: self.verbose,
+            }),
+        )
+    }
+}
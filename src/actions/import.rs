@@ -1,11 +1,118 @@
-use crate::actions::UnlockingAction;
+use crate::actions::{unlock_with_retries, UnlockingAction};
+use crate::import::{self, ColumnMapping, CsvOptions, ImportFormat, ImportedCredentials};
 use crate::store;
-use crate::vault::entities::Error;
+use crate::vault::entities::{Credential, Error};
 use crate::vault::vault_trait::Vault;
 use clap::ArgMatches;
+use std::collections::HashMap;
+
+// Imports where more than this share of the rows have the same password are considered suspicious
+const MAX_SHARED_PASSWORD_RATIO: f64 = 0.5;
+const MIN_ROWS_FOR_SHARED_PASSWORD_CHECK: usize = 4;
+const MAX_MASTER_PASSWORD_ATTEMPTS: u8 = 3;
 
 pub struct ImportCsvAction {
     pub file_path: String,
+    pub force: bool,
+    pub recovery_codes: bool,
+    pub format: String,
+    pub on_conflict: String,
+    pub keyfile: Option<String>,
+    pub delimiter: Option<String>,
+    pub columns: Option<String>,
+    pub strict: bool,
+}
+
+// What to do with an imported credential that has the same service and username as a saved one
+#[derive(Clone, Copy, PartialEq)]
+enum OnConflict {
+    Skip,
+    Update,
+    Duplicate,
+}
+
+impl OnConflict {
+    fn new(name: &str) -> Result<OnConflict, Error> {
+        match name {
+            "skip" => Ok(OnConflict::Skip),
+            "update" => Ok(OnConflict::Update),
+            "duplicate" => Ok(OnConflict::Duplicate),
+            _ => Err(Error::new(&format!("Unknown conflict mode '{}'", name))),
+        }
+    }
+}
+
+#[derive(Default)]
+struct MergeResult {
+    inserts: Vec<Credential>,
+    updates: Vec<Credential>,
+    skipped: usize,
+}
+
+fn is_same_credential(a: &Credential, b: &Credential) -> bool {
+    a.service().eq_ignore_ascii_case(b.service()) && a.username() == b.username()
+}
+
+// Matches the imported credentials against the saved ones and the earlier rows of the file
+fn merge_credentials(
+    existing: &[Credential],
+    imported: Vec<Credential>,
+    on_conflict: OnConflict,
+) -> MergeResult {
+    let mut result = MergeResult::default();
+    if on_conflict == OnConflict::Duplicate {
+        result.inserts = imported;
+        return result;
+    }
+    for cred in imported {
+        if let Some(earlier) = result
+            .inserts
+            .iter_mut()
+            .chain(result.updates.iter_mut())
+            .find(|earlier| is_same_credential(earlier, &cred))
+        {
+            if on_conflict == OnConflict::Update && cred.last_modified() >= earlier.last_modified()
+            {
+                *earlier = Credential::new(
+                    Some(earlier.uuid()),
+                    cred.password(),
+                    earlier.service(),
+                    earlier.username(),
+                    Some(*cred.last_modified()),
+                    earlier.tags(),
+                    cred.notes().or(earlier.notes()),
+                )
+                .with_no_username(!earlier.has_username());
+            }
+            result.skipped += 1;
+            continue;
+        }
+        match existing
+            .iter()
+            .find(|saved| is_same_credential(saved, &cred))
+        {
+            Some(saved)
+                if on_conflict == OnConflict::Update
+                    && cred.last_modified() >= saved.last_modified() =>
+            {
+                result.updates.push(
+                    Credential::new(
+                        Some(saved.uuid()),
+                        cred.password(),
+                        saved.service(),
+                        saved.username(),
+                        Some(*cred.last_modified()),
+                        saved.tags(),
+                        cred.notes().or(saved.notes()),
+                    )
+                    .with_no_username(!saved.has_username()),
+                )
+            }
+            Some(_) => result.skipped += 1,
+            None => result.inserts.push(cred),
+        }
+    }
+    result
 }
 
 impl ImportCsvAction {
@@ -15,21 +122,273 @@ impl ImportCsvAction {
                 .get_one::<String>("FILE_PATH")
                 .expect("required")
                 .to_string(),
+            force: matches.get_flag("force"),
+            recovery_codes: matches.get_flag("recovery"),
+            format: matches
+                .get_one::<String>("from")
+                .cloned()
+                .unwrap_or("passlane".to_string()),
+            on_conflict: matches
+                .get_one::<String>("on-conflict")
+                .cloned()
+                .unwrap_or("skip".to_string()),
+            keyfile: matches.get_one::<String>("key-file").cloned(),
+            delimiter: matches.get_one::<String>("delimiter").cloned(),
+            columns: matches.get_one::<String>("columns").cloned(),
+            strict: matches.get_flag("strict"),
         }
     }
 }
 
-fn push_from_csv(vault: &mut Box<dyn Vault>, file_path: &str) -> Result<i64, Error> {
-    let creds = store::read_from_csv(file_path)?;
-    vault.save_credentials(&creds)?;
-    let num_imported = creds.len();
-    Ok(num_imported.try_into().unwrap())
+fn find_suspicious_content(headers: &[String], creds: &[Credential]) -> Option<String> {
+    if let Some(header) = headers.iter().find(|h| h.to_lowercase().contains("master")) {
+        return Some(format!(
+            "The file has a column '{}' that looks like it holds a master password",
+            header
+        ));
+    }
+    if creds.len() >= MIN_ROWS_FOR_SHARED_PASSWORD_CHECK {
+        let mut counts: HashMap<&str, usize> = HashMap::new();
+        for cred in creds {
+            *counts.entry(cred.password()).or_insert(0) += 1;
+        }
+        let most_shared = counts.values().max().copied().unwrap_or(0);
+        if most_shared as f64 / creds.len() as f64 > MAX_SHARED_PASSWORD_RATIO {
+            return Some(format!(
+                "{} of the {} rows have the same password, the columns are probably mislabeled",
+                most_shared,
+                creds.len()
+            ));
+        }
+    }
+    None
+}
+
+fn push_from_csv(
+    vault: &mut Box<dyn Vault>,
+    imported: ImportedCredentials,
+    force: bool,
+    on_conflict: OnConflict,
+) -> Result<String, Error> {
+    if !force {
+        if let Some(warning) = find_suspicious_content(&imported.headers, &imported.credentials) {
+            return Err(Error::new(&format!(
+                "Warning: {}. Nothing was imported, use --force to import anyway.",
+                warning
+            )));
+        }
+    }
+    let merged = merge_credentials(&vault.grep(None), imported.credentials, on_conflict);
+    vault.save_credentials(&merged.inserts)?;
+    for cred in &merged.updates {
+        vault.update_credential(cred.clone())?;
+    }
+    let message = format!(
+        "Imported {} new entries, updated {} and skipped {} duplicates",
+        merged.inserts.len(),
+        merged.updates.len(),
+        merged.skipped
+    );
+    if imported.skipped.is_empty() {
+        return Ok(message);
+    }
+    Ok(format!(
+        "{}, skipped {} that could not be imported:\n{}",
+        message,
+        imported.skipped.len(),
+        imported.skipped.join("\n")
+    ))
+}
+
+fn push_recovery_codes_from_csv(vault: &mut Box<dyn Vault>, file_path: &str) -> Result<i64, Error> {
+    let recovery_codes = store::read_recovery_codes_from_csv(file_path)?;
+    for codes in &recovery_codes {
+        vault.save_recovery_codes(codes)?;
+    }
+    Ok(recovery_codes.len() as i64)
 }
 
 impl UnlockingAction for ImportCsvAction {
+    fn execute(&self) -> Result<Option<String>, Error> {
+        // verify the master password before parsing the file so that a typo doesn't waste a full import
+        self.run_with_vault(&mut unlock_with_retries(MAX_MASTER_PASSWORD_ATTEMPTS)?)
+    }
+
     fn run_with_vault(&self, vault: &mut Box<dyn Vault>) -> Result<Option<String>, Error> {
-        push_from_csv(vault, &self.file_path)
-            .map(|count| format!("Imported {} entries", count))
-            .map(Some)
+        if self.recovery_codes {
+            return push_recovery_codes_from_csv(vault, &self.file_path)
+                .map(|count| format!("Imported {} recovery code entries", count))
+                .map(Some);
+        }
+        let format = ImportFormat::new(&self.format)?;
+        let on_conflict = OnConflict::new(&self.on_conflict)?;
+        let csv = CsvOptions {
+            delimiter: match &self.delimiter {
+                Some(delimiter) => import::parse_delimiter(delimiter)?,
+                None => CsvOptions::default().delimiter,
+            },
+            columns: self
+                .columns
+                .as_deref()
+                .map(ColumnMapping::new)
+                .transpose()?,
+        };
+        let imported =
+            import::read_credentials(&self.file_path, format, self.keyfile.as_deref(), &csv)?;
+        if self.strict && !imported.skipped.is_empty() {
+            return Err(Error::new(&format!(
+                "Nothing was imported because of --strict, {} entries could not be imported:\n{}",
+                imported.skipped.len(),
+                imported.skipped.join("\n")
+            )));
+        }
+        push_from_csv(vault, imported, self.force, on_conflict).map(Some)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::{test_dir, test_file};
+    use crate::vault::keepass_vault::KeepassVault;
+
+    fn credential(service: &str, username: &str, password: &str) -> Credential {
+        Credential::new(None, password, service, username, None, &[], None)
+    }
+
+    fn headers(names: &[&str]) -> Vec<String> {
+        names.iter().map(|name| name.to_string()).collect()
+    }
+
+    fn dated(service: &str, password: &str, days_ago: i64) -> Credential {
+        let modified = chrono::Utc::now() - chrono::Duration::days(days_ago);
+        Credential::new(None, password, service, "john", Some(modified), &[], None)
+    }
+
+    fn passwords(credentials: &[Credential]) -> Vec<&str> {
+        credentials.iter().map(|c| c.password()).collect()
+    }
+
+    // A saved credential, a newer and an older row for it, and a new one twice in the file
+    fn merge(on_conflict: OnConflict) -> (Credential, MergeResult) {
+        let saved = dated("example.com", "saved", 10);
+        let imported = vec![
+            dated("EXAMPLE.com", "newer", 1),
+            dated("example.com", "older", 20),
+            dated("new.com", "first", 5),
+            dated("new.com", "second", 2),
+        ];
+        let result = merge_credentials(std::slice::from_ref(&saved), imported, on_conflict);
+        (saved, result)
+    }
+
+    #[test]
+    fn skip_keeps_the_saved_and_the_first_rows() {
+        let (_, result) = merge(OnConflict::Skip);
+        assert_eq!(passwords(&result.inserts), ["first"]);
+        assert!(result.updates.is_empty());
+        assert_eq!(result.skipped, 3);
+    }
+
+    #[test]
+    fn update_takes_the_newer_passwords() {
+        let (saved, result) = merge(OnConflict::Update);
+        assert_eq!(passwords(&result.inserts), ["second"]);
+        assert_eq!(passwords(&result.updates), ["newer"]);
+        let update = &result.updates[0];
+        assert_eq!(update.uuid(), saved.uuid());
+        assert_eq!(update.service(), "example.com");
+        assert_eq!(result.skipped, 2);
+    }
+
+    #[test]
+    fn duplicate_inserts_every_row() {
+        let (_, result) = merge(OnConflict::Duplicate);
+        assert_eq!(
+            passwords(&result.inserts),
+            ["newer", "older", "first", "second"]
+        );
+        assert!(result.updates.is_empty());
+        assert_eq!(result.skipped, 0);
+    }
+
+    fn import_action(args: &[&str]) -> ImportCsvAction {
+        let matches = crate::cli().get_matches_from([&["passlane", "csv"], args].concat());
+        ImportCsvAction::new(matches.subcommand_matches("csv").unwrap())
+    }
+
+    fn empty_vault(name: &str) -> Box<dyn Vault> {
+        let path = test_dir(name).join("store.kdbx");
+        Box::new(KeepassVault::new(&path.to_string_lossy(), "test", None).unwrap())
+    }
+
+    const FILE_WITH_A_BAD_ROW: &str =
+        "password,service,username\npw1,example.com,john\n,empty.com,jane\npw3,other.org,joe\n";
+
+    #[test]
+    fn strict_imports_nothing_when_a_row_is_bad() {
+        let path = test_file("strict.csv", FILE_WITH_A_BAD_ROW);
+        let mut vault = empty_vault("strict-vault");
+        let result = import_action(&["--strict", &path]).run_with_vault(&mut vault);
+        assert_eq!(
+            result.err().map(|e| e.message).as_deref(),
+            Some("Nothing was imported because of --strict, 1 entries could not be imported:\nline 3: no password")
+        );
+        assert!(vault.grep(None).is_empty());
+    }
+
+    #[test]
+    fn without_strict_the_bad_row_is_skipped() {
+        let path = test_file("not-strict.csv", FILE_WITH_A_BAD_ROW);
+        let mut vault = empty_vault("not-strict-vault");
+        let message = import_action(&[&path])
+            .run_with_vault(&mut vault)
+            .unwrap()
+            .unwrap();
+        assert!(message.ends_with("line 3: no password"), "{}", message);
+        assert_eq!(vault.grep(None).len(), 2);
+    }
+
+    #[test]
+    fn master_column_is_suspicious() {
+        let creds = [credential("example.com", "john", "pw1")];
+        let warning = find_suspicious_content(
+            &headers(&["service", "username", "Master Password"]),
+            &creds,
+        );
+        assert_eq!(
+            warning.as_deref(),
+            Some("The file has a column 'Master Password' that looks like it holds a master password")
+        );
+    }
+
+    #[test]
+    fn most_rows_sharing_a_password_is_suspicious() {
+        let creds = [
+            credential("a.com", "john", "same"),
+            credential("b.com", "john", "same"),
+            credential("c.com", "john", "same"),
+            credential("d.com", "john", "other"),
+        ];
+        let warning =
+            find_suspicious_content(&headers(&["service", "username", "password"]), &creds);
+        assert_eq!(
+            warning.as_deref(),
+            Some("3 of the 4 rows have the same password, the columns are probably mislabeled")
+        );
+    }
+
+    #[test]
+    fn ordinary_file_is_not_suspicious() {
+        let creds = [
+            credential("a.com", "john", "same"),
+            credential("b.com", "john", "same"),
+            credential("c.com", "john", "pw3"),
+            credential("d.com", "john", "pw4"),
+        ];
+        let headers = headers(&["service", "username", "password"]);
+        assert!(find_suspicious_content(&headers, &creds).is_none());
+        // too few rows to tell a shared password from a mislabeled column
+        assert!(find_suspicious_content(&headers, &creds[..2]).is_none());
     }
 }
@@ -8,6 +8,7 @@ use crate::ui::input::{
 };
 use crate::vault::entities::Error;
 use crate::vault::keepass_vault::KeepassVault;
+use zeroize::Zeroizing;
 
 pub struct InitAction {}
 
@@ -39,9 +40,10 @@ impl InitAction {
     fn initialize_vault(&self) -> Result<(String, bool), Error> {
         if store::has_vault_path() {
             println!("Vault already configured");
-            return Ok((store::get_vault_path(), false));
+            return Ok((store::get_vault_path()?, false));
         }
 
+        let default_path = store::get_vault_path()?;
         let (location, is_new_vault) = if ask_open_existing_vault() {
             (
                 self.get_and_save_vault_location(ask_existing_path, "Vault")?,
@@ -49,10 +51,7 @@ impl InitAction {
             )
         } else {
             (
-                self.get_and_save_vault_location(
-                    || ask_vault_path(&store::get_vault_path()),
-                    "Vault",
-                )?,
+                self.get_and_save_vault_location(|| ask_vault_path(&default_path), "Vault")?,
                 true,
             )
         };
@@ -62,16 +61,14 @@ impl InitAction {
     fn initialize_totp_vault(&self) -> Result<String, Error> {
         if store::has_totp_vault_path() {
             println!("TOTP Vault already configured");
-            return Ok(store::get_totp_vault_path());
+            return store::get_totp_vault_path();
         }
 
+        let default_path = store::get_totp_vault_path()?;
         let location = if ask_open_existing_totp_vault() {
             self.get_and_save_vault_location(ask_existing_path, "TOTP Vault")?
         } else {
-            self.get_and_save_vault_location(
-                || ask_totp_vault_path(&store::get_totp_vault_path()),
-                "TOTP Vault",
-            )?
+            self.get_and_save_vault_location(|| ask_totp_vault_path(&default_path), "TOTP Vault")?
         };
 
         Ok(location)
@@ -102,9 +99,9 @@ impl InitAction {
     fn init_keyfile(&self) -> Result<Option<String>, Error> {
         if store::has_keyfile_path() {
             println!("Keyfile already configured");
-            return Ok(store::get_keyfile_path());
+            return store::get_keyfile_path();
         }
-        let keyfile_location = ask_keyfile_path(store::get_keyfile_path().as_deref());
+        let keyfile_location = ask_keyfile_path(store::get_keyfile_path()?.as_deref());
         if let Some(keyfile) = &keyfile_location {
             if keyfile != "" {
                 store::save_keyfile_path(keyfile)?;
@@ -113,17 +110,17 @@ impl InitAction {
         Ok(keyfile_location)
     }
 
-    fn initialize_master_password(&self) -> Result<String, Error> {
+    fn initialize_master_password(&self) -> Result<Zeroizing<String>, Error> {
         println!("Initializing master password... checking if already stored in keychain");
         let master_pwd = keychain::get_master_password();
         match master_pwd {
             Ok(pwd) => {
                 println!("Master password already configured");
-                Ok(pwd)
+                Ok(Zeroizing::new(pwd))
             }
             Err(_) => {
                 println!("Initializing a new master password");
-                let master_pwd = ask_new_master_password();
+                let master_pwd = Zeroizing::new(ask_new_master_password());
                 if ask_store_master_password() {
                     keychain::save_master_password(&master_pwd)?;
                 }
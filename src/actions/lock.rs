@@ -1,11 +1,14 @@
 use crate::actions::Action;
 use crate::keychain;
+use crate::store;
 use crate::vault::entities::Error;
 
 pub struct LockAction {}
 
 impl Action for LockAction {
     fn run(&self) -> Result<String, Error> {
+        store::clear_unlock_expiry(false)?;
+        store::clear_unlock_expiry(true)?;
         let credential_vault_response = match keychain::delete_master_password() {
             Ok(_) => {
                 "Vault locked"
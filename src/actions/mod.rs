@@ -1,25 +1,126 @@
 pub mod add;
+pub mod backup_key;
+pub mod check;
+pub mod clear_clipboard;
+pub mod completions;
 pub mod delete;
+pub mod diff;
 pub mod edit;
 pub mod export;
 pub mod generate;
 pub mod help;
+pub mod history;
 pub mod import;
 pub mod init;
 pub mod lock;
+pub mod move_service;
+pub mod open;
+pub mod profiles;
+pub mod recovery;
+pub mod restore_key;
+pub mod rotate_all;
 pub mod show;
+pub mod stats;
+pub mod status;
+pub mod sync;
 pub mod unlock;
 
+use crate::clipboard;
+use crate::config;
+use crate::crypto::{self, PasswordGenerator, PasswordSpec};
 use crate::keychain;
 use crate::store;
 
 use crate::ui::input::{ask_master_password, ask_totp_master_password};
-use crate::vault::entities::Error;
+use crate::vault::entities::{Credential, Error};
 use crate::vault::keepass_vault::KeepassVault;
 use crate::vault::vault_trait::Vault;
 use clap::ArgMatches;
-use clipboard::ClipboardContext;
-use clipboard::ClipboardProvider;
+use regex::{Regex, RegexBuilder};
+use serde::{Deserialize, Serialize};
+use std::env;
+use std::io::{self, Write};
+use std::process::{Command, Stdio};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Mutex, OnceLock};
+use std::thread;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use zeroize::Zeroizing;
+
+pub(crate) fn password_generator_from_args(matches: &ArgMatches) -> PasswordGenerator {
+    if let Some(word_count) = matches.get_one::<usize>("words") {
+        return PasswordGenerator::Passphrase {
+            word_count: *word_count,
+            separator: matches
+                .get_one::<String>("separator")
+                .cloned()
+                .unwrap_or("-".to_string()),
+        };
+    }
+    if let Some(name) = matches.get_one::<String>("preset") {
+        return PasswordGenerator::Preset {
+            name: name.clone(),
+            length: matches.get_one::<usize>("length").copied(),
+        };
+    }
+    let defaults = default_password_spec();
+    if matches.get_flag("pronounceable") {
+        return PasswordGenerator::Pronounceable {
+            length: matches
+                .get_one::<usize>("length")
+                .copied()
+                .unwrap_or(defaults.length),
+            with_digits: matches.get_flag("digits"),
+        };
+    }
+    PasswordGenerator::Random(PasswordSpec {
+        length: matches
+            .get_one::<usize>("length")
+            .copied()
+            .unwrap_or(defaults.length),
+        include_symbols: !matches.get_flag("no-symbols"),
+        exclude_ambiguous: matches.get_flag("no-ambiguous"),
+        ..defaults
+    })
+}
+
+/// Tells how much entropy leaving out the ambiguous characters costs, so that the length can be chosen to match
+pub(crate) fn report_ambiguous_entropy(generator: &PasswordGenerator) {
+    if let PasswordGenerator::Random(spec) = generator {
+        if spec.exclude_ambiguous {
+            let bits = crypto::random_entropy_bits(spec);
+            let with_ambiguous = crypto::random_entropy_bits(&PasswordSpec {
+                exclude_ambiguous: false,
+                ..spec.clone()
+            });
+            eprintln!(
+                "The password has about {:.0} bits of entropy, {:.0} bits less than with the ambiguous characters",
+                bits,
+                with_ambiguous - bits
+            );
+        }
+    }
+}
+
+/// Whether to indent the JSON output, None to decide by whether it's printed to a terminal
+pub(crate) fn json_pretty_from_args(matches: &ArgMatches) -> Option<bool> {
+    if matches.get_flag("pretty") {
+        Some(true)
+    } else if matches.get_flag("compact") {
+        Some(false)
+    } else {
+        None
+    }
+}
+
+/// The password spec with the length from the config
+pub(crate) fn default_password_spec() -> PasswordSpec {
+    let defaults = PasswordSpec::default();
+    PasswordSpec {
+        length: config::get().password_length.unwrap_or(defaults.length),
+        ..defaults
+    }
+}
 
 pub(crate) trait MatchHandlerTemplate
 where
@@ -35,6 +136,34 @@ where
     ) -> Result<Option<String>, Error>;
 }
 
+// Exit codes for scripts, 1 is used for all other errors.
+// The exact service lookup of show exits with the same code when nothing matches.
+pub(crate) const EXIT_NOT_FOUND: i32 = 2;
+pub(crate) const EXIT_MULTIPLE_MATCHES: i32 = 3;
+
+static FAIL_ON_EMPTY: AtomicBool = AtomicBool::new(false);
+
+/// Makes a search that finds nothing exit with `EXIT_NOT_FOUND` instead of succeeding
+pub(crate) fn set_fail_on_empty(fail: bool) {
+    FAIL_ON_EMPTY.store(fail, Ordering::Relaxed);
+}
+
+/// Prints the message to stderr and exits. A value copied by this run is still cleared when the
+/// clipboard timeout has passed.
+pub(crate) fn exit_with(code: i32, message: &str) -> ! {
+    eprintln!("{}", message);
+    clear_clipboard_later();
+    std::process::exit(code)
+}
+
+/// The result of a search that found nothing, exits if `--fail-on-empty` was given
+pub(crate) fn no_matches() -> Result<Option<String>, Error> {
+    if FAIL_ON_EMPTY.load(Ordering::Relaxed) {
+        exit_with(EXIT_NOT_FOUND, "No matches found");
+    }
+    Ok(Some("No matches found".to_string()))
+}
+
 pub(crate) fn handle_matches<H>(
     matches: Vec<H::ItemType>,
     handler: &mut Box<H>,
@@ -44,7 +173,7 @@ where
     H::ItemType: Clone,
 {
     if matches.is_empty() {
-        Ok(Some("No matches found".to_string()))
+        no_matches()
     } else {
         handler.pre_handle_matches(&matches.clone());
 
@@ -62,42 +191,335 @@ pub trait Action {
     }
 }
 
-fn get_vault_properties() -> (String, String, Option<String>) {
-    let stored_password = keychain::get_master_password();
-    let master_pwd = stored_password.unwrap_or_else(|_| ask_master_password(None));
-    let filepath = store::get_vault_path();
-    let keyfile_path = store::get_keyfile_path();
-    (master_pwd, filepath, keyfile_path)
+const MASTER_PASSWORD_ENV: &str = "PASSLANE_MASTER_PASSWORD";
+
+static MASTER_PASSWORD_FILE: OnceLock<String> = OnceLock::new();
+
+/// Makes the master password of the vault be read from this file, before the environment and the keychain
+pub(crate) fn set_master_password_file(path: &str) {
+    let _ = MASTER_PASSWORD_FILE.set(path.to_string());
+}
+
+// The master password and whether it was asked instead of read from a file, the environment or the keychain
+const NO_PIPED_MASTER_PASSWORD: &str =
+    "No master password was piped to stdin, and there's no terminal to ask it from";
+
+fn get_master_password() -> Result<(Zeroizing<String>, bool), Error> {
+    if let Some(path) = MASTER_PASSWORD_FILE.get() {
+        return Ok((store::read_master_password_file(path)?, false));
+    }
+    if let Ok(master_pwd) = std::env::var(MASTER_PASSWORD_ENV) {
+        eprintln!(
+            "Warning: using the master password from {}, other processes of your user may be able to read it",
+            MASTER_PASSWORD_ENV
+        );
+        return Ok((Zeroizing::new(master_pwd), false));
+    }
+    lock_if_expired(false);
+    match keychain::get_master_password() {
+        Ok(master_pwd) => Ok((Zeroizing::new(master_pwd), false)),
+        Err(_) => match ask_master_password(None) {
+            Some(master_pwd) => Ok((Zeroizing::new(master_pwd), true)),
+            None => Err(Error::new(NO_PIPED_MASTER_PASSWORD)),
+        },
+    }
+}
+
+fn get_vault_properties() -> Result<(Zeroizing<String>, bool, String, Option<String>), Error> {
+    let filepath = store::get_vault_path()?;
+    let keyfile_path = store::get_keyfile_path()?;
+    let (master_pwd, asked) = get_master_password()?;
+    Ok((master_pwd, asked, filepath, keyfile_path))
+}
+
+// With `keychain_default` in the config an asked password is kept in the keychain like `unlock` does
+fn remember_master_password(vault: &dyn Vault, asked: bool, save: fn(&str) -> Result<(), Error>) {
+    if asked && config::get().keychain_default {
+        if let Err(e) = save(&Zeroizing::new(vault.get_master_password())) {
+            eprintln!("Failed to save the master password to the keychain: {}", e);
+        }
+    }
+}
+
+// Removes the master password from the keychain when the time given to `unlock --for` has run out
+fn lock_if_expired(totp: bool) {
+    if !unlock_has_expired(store::get_unlock_expiry(totp), unix_time()) {
+        return;
+    }
+    // the password may have been removed already with `lock`
+    let _ = if totp {
+        keychain::delete_totp_master_password()
+    } else {
+        keychain::delete_master_password()
+    };
+    if let Err(e) = store::clear_unlock_expiry(totp) {
+        eprintln!("Failed to clear the unlock time: {}", e);
+    }
+    eprintln!("The unlock time ran out, the vault was locked");
+}
+
+// Without an expiry the keychain password was saved with a plain `unlock` and is kept
+fn unlock_has_expired(expires: Option<u64>, now: u64) -> bool {
+    expires.is_some_and(|expires| now >= expires)
+}
+
+// Wrong master passwords allowed before each attempt has to wait
+const FREE_UNLOCK_ATTEMPTS: u32 = 5;
+const MAX_UNLOCK_DELAY_SECONDS: u64 = 60;
+
+fn unix_time() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |duration| duration.as_secs())
+}
+
+// The delay doubles with each wrong master password after the free attempts: 2, 4, 8... seconds
+fn unlock_delay_seconds(failures: u32) -> u64 {
+    if failures < FREE_UNLOCK_ATTEMPTS {
+        return 0;
+    }
+    2u64.saturating_pow(failures - FREE_UNLOCK_ATTEMPTS + 1)
+        .min(MAX_UNLOCK_DELAY_SECONDS)
+}
+
+fn wait_for_unlock_delay(totp: bool) {
+    let Some((failures, last_failure)) = store::get_unlock_failures(totp) else {
+        return;
+    };
+    let wait_until = last_failure + unlock_delay_seconds(failures);
+    let now = unix_time();
+    if wait_until > now {
+        eprintln!(
+            "{} wrong master passwords in a row, waiting {}s before trying again...",
+            failures,
+            wait_until - now
+        );
+        thread::sleep(Duration::from_secs(wait_until - now));
+    }
+}
+
+// Opens the vault, delaying the attempt after repeated wrong master passwords of the same vault.
+// Returns None when the master password is wrong.
+fn open_vault(
+    password: &str,
+    filepath: &str,
+    keyfile_path: Option<String>,
+    totp: bool,
+) -> Result<Option<KeepassVault>, Error> {
+    wait_for_unlock_delay(totp);
+    let vault = KeepassVault::verify_master_password(password, filepath, keyfile_path)?;
+    let result = if vault.is_some() {
+        store::clear_unlock_failures(totp)
+    } else {
+        let failures = store::get_unlock_failures(totp).map_or(0, |(count, _)| count);
+        store::save_unlock_failures(totp, failures + 1, unix_time())
+    };
+    if let Err(e) = result {
+        eprintln!("Failed to save the count of wrong master passwords: {}", e);
+    }
+    Ok(vault)
 }
 
 fn unlock() -> Result<Box<dyn Vault>, Error> {
-    let (master_pwd, filepath, keyfile_path) = get_vault_properties();
-    println!("Unlocking vault...");
-    get_vault(&master_pwd, &filepath, keyfile_path)
+    let (master_pwd, asked, filepath, keyfile_path) = get_vault_properties()?;
+    eprintln!("Unlocking vault...");
+    let vault = get_vault(&master_pwd, &filepath, keyfile_path, false)?;
+    remember_master_password(vault.as_ref(), asked, keychain::save_master_password);
+    Ok(vault)
+}
+
+// Unlocks the vault, asking the master password again when it's wrong
+fn unlock_with_retries(max_attempts: u8) -> Result<Box<dyn Vault>, Error> {
+    let (mut master_pwd, asked, filepath, keyfile_path) = get_vault_properties()?;
+    eprintln!("Unlocking vault...");
+    for attempt in 1..=max_attempts {
+        if let Some(vault) = open_vault(&master_pwd, &filepath, keyfile_path.clone(), false)? {
+            remember_master_password(&vault, asked, keychain::save_master_password);
+            return Ok(Box::new(vault));
+        }
+        if attempt < max_attempts {
+            // an empty stdin is not another attempt, there's nothing left to try
+            match ask_master_password(Some("Wrong master password, please try again")) {
+                Some(password) => master_pwd = Zeroizing::new(password),
+                None => return Err(Error::new("Wrong master password")),
+            }
+        }
+    }
+    Err(Error::new(&format!(
+        "Wrong master password {} times, giving up",
+        max_attempts
+    )))
 }
 
 fn unlock_totp_vault() -> Result<Box<dyn Vault>, Error> {
-    let stored_password = keychain::get_totp_master_password();
-    let master_pwd = stored_password.unwrap_or_else(|_| ask_totp_master_password());
-    let filepath = store::get_totp_vault_path();
-    let keyfile_path = store::get_totp_keyfile_path();
-    println!("Unlocking TOTP vault...");
-    get_vault(&master_pwd, &filepath, keyfile_path)
+    let filepath = store::get_totp_vault_path()?;
+    let keyfile_path = store::get_totp_keyfile_path()?;
+    lock_if_expired(true);
+    let (master_pwd, asked) = match keychain::get_totp_master_password() {
+        Ok(master_pwd) => (Zeroizing::new(master_pwd), false),
+        Err(_) => match ask_totp_master_password() {
+            Some(master_pwd) => (Zeroizing::new(master_pwd), true),
+            None => return Err(Error::new(NO_PIPED_MASTER_PASSWORD)),
+        },
+    };
+    eprintln!("Unlocking TOTP vault...");
+    let vault = get_vault(&master_pwd, &filepath, keyfile_path, true)?;
+    remember_master_password(vault.as_ref(), asked, keychain::save_totp_master_password);
+    Ok(vault)
 }
 
 fn get_vault(
     password: &str,
     filepath: &str,
     keyfile_path: Option<String>,
+    totp: bool,
 ) -> Result<Box<dyn Vault>, Error> {
     // we could return some other Vault implementation here
-    let vault = KeepassVault::open(password, filepath, keyfile_path)?;
-    Ok(Box::new(vault))
+    match open_vault(password, filepath, keyfile_path, totp)? {
+        Some(vault) => Ok(Box::new(vault)),
+        None => Err(Error::new("Wrong master password")),
+    }
 }
 
-pub fn copy_to_clipboard(value: &str) {
-    let mut ctx: ClipboardContext = ClipboardProvider::new().unwrap();
-    ctx.set_contents(String::from(value)).unwrap();
+const DEFAULT_CLIPBOARD_TIMEOUT_SECONDS: u64 = 30;
+
+// The value copied by this run and what the clipboard had before it
+#[derive(Serialize, Deserialize)]
+pub(crate) struct PendingClear {
+    value: Zeroizing<String>,
+    previous: Option<Zeroizing<String>>,
+}
+
+static PENDING_CLEAR: Mutex<Option<PendingClear>> = Mutex::new(None);
+
+static CLIPBOARD_TIMEOUT: AtomicU64 = AtomicU64::new(DEFAULT_CLIPBOARD_TIMEOUT_SECONDS);
+
+/// Sets the seconds after which a copied value is cleared, zero leaves it in the clipboard
+pub(crate) fn set_clipboard_timeout(seconds: u64) {
+    CLIPBOARD_TIMEOUT.store(seconds, Ordering::Relaxed);
+}
+
+/// Copies the value to the clipboard. The value is cleared later by `clear_clipboard_later`.
+pub fn copy_to_clipboard(value: &str) -> Result<(), Error> {
+    let previous = clipboard::get_contents().ok().map(Zeroizing::new);
+    clipboard::set(value)?;
+    let mut pending = PENDING_CLEAR.lock().unwrap();
+    // when copying several values keep the contents from before the first one
+    let previous = match pending.take() {
+        Some(earlier) => earlier.previous,
+        None => previous,
+    };
+    *pending = Some(PendingClear {
+        value: Zeroizing::new(value.to_string()),
+        previous,
+    });
+    Ok(())
+}
+
+/// Copies the password to the clipboard. When there is no clipboard, for example over SSH,
+/// the password is printed instead and false is returned.
+pub(crate) fn copy_or_print(password: &str) -> bool {
+    copy_or_print_with(password, copy_to_clipboard, &mut io::stdout())
+}
+
+// Copies with the given clipboard, the password is printed to `out` when copying fails
+fn copy_or_print_with(
+    password: &str,
+    copy: impl FnOnce(&str) -> Result<(), Error>,
+    out: &mut dyn Write,
+) -> bool {
+    match copy(password) {
+        Ok(()) => true,
+        Err(e) => {
+            eprintln!("No clipboard available; password not copied ({})", e);
+            let _ = writeln!(out, "{}", password);
+            false
+        }
+    }
+}
+
+pub(crate) fn clipboard_timeout(matches: &ArgMatches) -> u64 {
+    matches
+        .get_one::<u64>("clear-after")
+        .copied()
+        .or(config::get().clipboard_timeout)
+        .unwrap_or(DEFAULT_CLIPBOARD_TIMEOUT_SECONDS)
+}
+
+/// Hands the copied value over to a `passlane clear-clipboard --after` process that clears it
+/// when the timeout has passed, so that this run can end right away. The values are written to
+/// its stdin to keep them out of the process list. Nothing is done if the timeout is zero.
+pub(crate) fn clear_clipboard_later() {
+    let Some(pending) = PENDING_CLEAR.lock().unwrap().take() else {
+        return;
+    };
+    let seconds = CLIPBOARD_TIMEOUT.load(Ordering::Relaxed);
+    if seconds == 0 {
+        return;
+    }
+    match spawn_clipboard_clearer(seconds, &pending) {
+        Ok(()) => eprintln!("Clipboard will clear in {}s", seconds),
+        Err(e) => eprintln!("Failed to start clearing the clipboard: {}", e),
+    }
+}
+
+fn spawn_clipboard_clearer(seconds: u64, pending: &PendingClear) -> Result<(), Error> {
+    let mut command = Command::new(env::current_exe()?);
+    command
+        .args(["clear-clipboard", "--after", &seconds.to_string()])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null());
+    // in its own process group the helper is not stopped by a Ctrl-C in the terminal
+    #[cfg(unix)]
+    std::os::unix::process::CommandExt::process_group(&mut command, 0);
+    let mut child = command.spawn()?;
+    let stdin = child.stdin.take().expect("stdin is piped");
+    serde_json::to_writer(stdin, pending)?;
+    Ok(())
+}
+
+/// Run by the `clear-clipboard --after` helper process. Copies the value again so that it stays
+/// available after the run that copied it has ended, waits until the timeout and then restores
+/// what the clipboard had before. Nothing is restored if the user has copied something else in
+/// the meantime.
+pub(crate) fn restore_clipboard_after(seconds: u64, pending: PendingClear) -> Result<(), Error> {
+    clipboard::set(&pending.value)?;
+    thread::sleep(Duration::from_secs(seconds));
+    let current = clipboard::get_contents();
+    if current.is_ok_and(|current| current == *pending.value) {
+        clipboard::set(pending.previous.as_deref().map_or("", String::as_str))?;
+    }
+    Ok(())
+}
+
+/// Runs the user's on copy command, if configured, with the service as the last argument and in
+/// the PASSLANE_SERVICE environment variable. The password is never passed to the command.
+pub(crate) fn run_on_copy_command(service: &str) {
+    let command = match store::get_on_copy_command() {
+        Ok(Some(command)) => command,
+        Ok(None) => return,
+        Err(e) => {
+            eprintln!("Failed to read the on copy command: {}", e);
+            return;
+        }
+    };
+    let mut parts = command.split_whitespace();
+    let Some(program) = parts.next() else {
+        return;
+    };
+    let result = Command::new(program)
+        .args(parts)
+        .arg(service)
+        .env("PASSLANE_SERVICE", service)
+        .status();
+    match result {
+        Ok(status) if !status.success() => {
+            eprintln!("The on copy command '{}' failed with {}", command, status)
+        }
+        Err(e) => eprintln!("Failed to run the on copy command '{}': {}", command, e),
+        _ => {}
+    }
 }
 
 pub trait UnlockingAction {
@@ -139,3 +561,149 @@ impl ItemType {
         }
     }
 }
+
+/// How the REGEXP argument is matched. It's plain text by default, so that the dots and pluses of
+/// service names match themselves, and a regular expression with `--regex`. Both ignore case.
+pub(crate) enum SearchPattern {
+    Literal(String),
+    Regex(Regex),
+}
+
+impl SearchPattern {
+    pub(crate) fn new(term: &str, regex: bool) -> Result<SearchPattern, Error> {
+        if !regex {
+            return Ok(SearchPattern::Literal(term.to_string()));
+        }
+        RegexBuilder::new(term)
+            .case_insensitive(true)
+            .build()
+            .map(SearchPattern::Regex)
+            .map_err(|e| Error::new(&format!("Invalid regular expression: {}", e)))
+    }
+
+    pub(crate) fn is_match(&self, value: &str) -> bool {
+        match self {
+            SearchPattern::Literal(term) => value.to_lowercase().contains(&term.to_lowercase()),
+            SearchPattern::Regex(regex) => regex.is_match(value),
+        }
+    }
+
+    /// The credentials whose service or username matches
+    pub(crate) fn find_credentials(&self, vault: &dyn Vault) -> Vec<Credential> {
+        match self {
+            SearchPattern::Literal(term) => vault.grep(Some(term)),
+            SearchPattern::Regex(_) => vault
+                .grep(None)
+                .into_iter()
+                .filter(|c| self.is_match(c.service()) || self.is_match(c.username()))
+                .collect(),
+        }
+    }
+
+    /// Deletes the credentials that `find_credentials` returns
+    pub(crate) fn delete_credentials(&self, vault: &mut Box<dyn Vault>) -> Result<usize, Error> {
+        match self {
+            SearchPattern::Literal(term) => vault.delete_matching(term),
+            SearchPattern::Regex(_) => {
+                let matches = self.find_credentials(vault.as_ref());
+                for credential in &matches {
+                    vault.delete_credentials(credential.uuid())?;
+                }
+                Ok(matches.len())
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn unlock_delay_grows_after_the_free_attempts() {
+        for failures in 0..FREE_UNLOCK_ATTEMPTS {
+            assert_eq!(unlock_delay_seconds(failures), 0);
+        }
+        let delays: Vec<u64> = (FREE_UNLOCK_ATTEMPTS..FREE_UNLOCK_ATTEMPTS + 5)
+            .map(unlock_delay_seconds)
+            .collect();
+        assert_eq!(delays, [2, 4, 8, 16, 32]);
+    }
+
+    #[test]
+    fn unlock_delay_is_capped() {
+        assert_eq!(unlock_delay_seconds(FREE_UNLOCK_ATTEMPTS + 5), 60);
+        assert_eq!(unlock_delay_seconds(u32::MAX), MAX_UNLOCK_DELAY_SECONDS);
+    }
+
+    #[test]
+    fn clipboard_failure_prints_the_password() {
+        let mut out = Vec::new();
+        let copied = copy_or_print_with(
+            "s3cret",
+            |_| Err(Error::new("Clipboard is not available")),
+            &mut out,
+        );
+        assert!(!copied);
+        assert_eq!(String::from_utf8(out).unwrap(), "s3cret\n");
+    }
+
+    #[test]
+    fn copied_password_is_not_printed() {
+        let mut copied_value = String::new();
+        let mut out = Vec::new();
+        let copied = copy_or_print_with(
+            "s3cret",
+            |value| {
+                copied_value = value.to_string();
+                Ok(())
+            },
+            &mut out,
+        );
+        assert!(copied);
+        assert_eq!(copied_value, "s3cret");
+        assert!(out.is_empty());
+    }
+
+    #[test]
+    fn literal_pattern_matches_the_dots_themselves() {
+        let pattern = SearchPattern::new("a.b", false).unwrap();
+        assert!(pattern.is_match("A.B.com"));
+        assert!(!pattern.is_match("axb.com"));
+    }
+
+    #[test]
+    fn regex_pattern_matches_any_character_for_a_dot() {
+        let pattern = SearchPattern::new("a.b", true).unwrap();
+        assert!(pattern.is_match("axb.com"));
+        assert!(pattern.is_match("AXB.com"));
+        assert!(!pattern.is_match("ab.com"));
+    }
+
+    #[test]
+    fn invalid_regex_is_an_error() {
+        let result = SearchPattern::new("[a-", true);
+        assert!(result
+            .err()
+            .is_some_and(|e| e.message.starts_with("Invalid regular expression: ")));
+    }
+
+    #[test]
+    fn keychain_password_is_kept_within_the_unlock_time() {
+        let expires = 1_000 + 30 * 60;
+        assert!(!unlock_has_expired(Some(expires), 1_000));
+        assert!(!unlock_has_expired(Some(expires), expires - 1));
+    }
+
+    #[test]
+    fn keychain_password_is_removed_after_the_unlock_time() {
+        let expires = 1_000 + 30 * 60;
+        assert!(unlock_has_expired(Some(expires), expires));
+        assert!(unlock_has_expired(Some(expires), expires + 60));
+    }
+
+    #[test]
+    fn unlock_without_a_time_never_expires() {
+        assert!(!unlock_has_expired(None, u64::MAX));
+    }
+}
@@ -0,0 +1,134 @@
+use crate::actions::{handle_matches, MatchHandlerTemplate, SearchPattern, UnlockingAction};
+use crate::ui::input::{ask_index, validate_service};
+use crate::ui::output::show_credentials_table;
+use crate::vault::entities::{Credential, Error};
+use crate::vault::vault_trait::Vault;
+use clap::ArgMatches;
+
+struct MoveTemplate<'a> {
+    vault: &'a mut Box<dyn Vault>,
+    service: &'a str,
+}
+
+// The credential with the new service. Only the service changes, the password history of the entry is kept.
+fn moved_credential(credential: &Credential, service: &str) -> Credential {
+    Credential::new(
+        Some(credential.uuid()),
+        credential.password(),
+        service,
+        credential.username(),
+        None,
+        credential.tags(),
+        credential.notes(),
+    )
+    .with_no_username(!credential.has_username())
+}
+
+impl<'a> MoveTemplate<'a> {
+    fn move_credential(&mut self, credential: &Credential) -> Result<Option<String>, Error> {
+        self.vault
+            .update_credential(moved_credential(credential, self.service))?;
+        Ok(Some(format!(
+            "Moved '{}' ({}) to '{}'",
+            credential.service(),
+            credential.username(),
+            self.service
+        )))
+    }
+}
+
+impl<'a> MatchHandlerTemplate for MoveTemplate<'a> {
+    type ItemType = Credential;
+
+    fn pre_handle_matches(&self, matches: &Vec<Self::ItemType>) {
+        println!("Found {} credentials:", matches.len());
+    }
+
+    fn handle_one_match(&mut self, the_match: Self::ItemType) -> Result<Option<String>, Error> {
+        self.move_credential(&the_match)
+    }
+
+    fn handle_many_matches(
+        &mut self,
+        matches: Vec<Self::ItemType>,
+    ) -> Result<Option<String>, Error> {
+        show_credentials_table(&matches, false);
+        match ask_index(
+            &format!(
+                "To move one of these to '{}', enter a row number from the table above",
+                self.service
+            ),
+            matches.len() as i16 - 1,
+            Some("Press q to exit without moving"),
+        ) {
+            Ok(index) => self.move_credential(&matches[index]),
+            Err(message) => Err(Error { message }),
+        }
+    }
+}
+
+/// Changes the service of a credential, keeping the password, the username and the other fields
+pub struct MoveAction {
+    pub grep: String,
+    pub service: String,
+    pub force: bool,
+    pub regex: bool,
+}
+
+impl MoveAction {
+    pub fn new(matches: &ArgMatches) -> MoveAction {
+        MoveAction {
+            grep: matches
+                .get_one::<String>("REGEXP")
+                .expect("required")
+                .to_string(),
+            service: matches
+                .get_one::<String>("SERVICE")
+                .expect("required")
+                .to_string(),
+            force: matches.get_flag("force"),
+            regex: matches.get_flag("regex"),
+        }
+    }
+}
+
+impl UnlockingAction for MoveAction {
+    fn run_with_vault(&self, vault: &mut Box<dyn Vault>) -> Result<Option<String>, Error> {
+        let service = validate_service(&self.service, self.force)
+            .map_err(|e| Error::new(&format!("{}, use --force to save it anyway", e)))?;
+        let matches = SearchPattern::new(&self.grep, self.regex)?.find_credentials(vault.as_ref());
+        handle_matches(
+            matches,
+            &mut Box::new(MoveTemplate {
+                vault,
+                service: &service,
+            }),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn moving_changes_only_the_service() {
+        let tags = vec!["work".to_string()];
+        let credential = Credential::new(
+            None,
+            "s3cret",
+            "https://twitter.com",
+            "john",
+            None,
+            &tags,
+            Some("recovery email"),
+        );
+        let moved = moved_credential(&credential, "https://x.com");
+        assert_eq!(moved.service(), "https://x.com");
+        assert_eq!(moved.uuid(), credential.uuid());
+        assert_eq!(moved.password(), "s3cret");
+        assert_eq!(moved.username(), "john");
+        assert_eq!(moved.tags(), tags);
+        assert_eq!(moved.notes(), Some("recovery email"));
+    }
+}
@@ -0,0 +1,114 @@
+use crate::actions::{
+    copy_or_print, handle_matches, run_on_copy_command, MatchHandlerTemplate, SearchPattern,
+    UnlockingAction,
+};
+use crate::ui::input::ask_index;
+use crate::ui::output::show_credentials_table;
+use crate::vault::entities::{Credential, Error};
+use crate::vault::vault_trait::Vault;
+use clap::ArgMatches;
+use std::process::Command;
+use url::Url;
+
+// The service is saved as the URL of the entry, names like "My bank" can't be opened
+fn service_url(service: &str) -> Result<Url, Error> {
+    let url = Url::parse(service)
+        .or_else(|_| Url::parse(&format!("https://{}", service)))
+        .ok()
+        .filter(|url| matches!(url.scheme(), "http" | "https"))
+        .filter(|url| url.host_str().is_some_and(|host| host.contains('.')));
+    url.ok_or_else(|| Error::new(&format!("The service '{}' is not a URL", service)))
+}
+
+fn open_in_browser(url: &Url) -> Result<(), Error> {
+    let mut command = if cfg!(target_os = "macos") {
+        Command::new("open")
+    } else if cfg!(windows) {
+        let mut command = Command::new("cmd");
+        command.args(["/C", "start", ""]);
+        command
+    } else {
+        Command::new("xdg-open")
+    };
+    match command.arg(url.as_str()).status() {
+        Ok(status) if status.success() => Ok(()),
+        Ok(status) => Err(Error::new(&format!(
+            "Failed to open {} in the browser, the command exited with {}",
+            url, status
+        ))),
+        Err(e) => Err(Error::new(&format!(
+            "Failed to open {} in the browser: {}",
+            url, e
+        ))),
+    }
+}
+
+struct OpenTemplate;
+
+impl OpenTemplate {
+    fn open(&self, credential: &Credential) -> Result<Option<String>, Error> {
+        let url = service_url(credential.service())?;
+        let copied = copy_or_print(credential.password());
+        if copied {
+            run_on_copy_command(credential.service());
+        }
+        open_in_browser(&url)?;
+        Ok(Some(if copied {
+            format!("Password copied to clipboard, opened {}", url)
+        } else {
+            format!("Opened {}", url)
+        }))
+    }
+}
+
+impl MatchHandlerTemplate for OpenTemplate {
+    type ItemType = Credential;
+
+    fn pre_handle_matches(&self, matches: &Vec<Self::ItemType>) {
+        println!("Found {} credentials:", matches.len());
+    }
+
+    fn handle_one_match(&mut self, the_match: Self::ItemType) -> Result<Option<String>, Error> {
+        show_credentials_table(std::slice::from_ref(&the_match), false);
+        self.open(&the_match)
+    }
+
+    fn handle_many_matches(
+        &mut self,
+        matches: Vec<Self::ItemType>,
+    ) -> Result<Option<String>, Error> {
+        show_credentials_table(&matches, false);
+        match ask_index(
+            "To open one of these services, enter a row number from the table above",
+            matches.len() as i16 - 1,
+            Some("The password is copied to clipboard. Press q to exit"),
+        ) {
+            Ok(index) => self.open(&matches[index]),
+            Err(message) => Err(Error { message }),
+        }
+    }
+}
+
+pub struct OpenAction {
+    pub grep: String,
+    pub regex: bool,
+}
+
+impl OpenAction {
+    pub fn new(matches: &ArgMatches) -> OpenAction {
+        OpenAction {
+            grep: matches
+                .get_one::<String>("REGEXP")
+                .expect("required")
+                .to_string(),
+            regex: matches.get_flag("regex"),
+        }
+    }
+}
+
+impl UnlockingAction for OpenAction {
+    fn run_with_vault(&self, vault: &mut Box<dyn Vault>) -> Result<Option<String>, Error> {
+        let matches = SearchPattern::new(&self.grep, self.regex)?.find_credentials(vault.as_ref());
+        handle_matches(matches, &mut Box::new(OpenTemplate))
+    }
+}
@@ -0,0 +1,30 @@
+use crate::actions::Action;
+use crate::store;
+use crate::vault::entities::Error;
+
+const DEFAULT_PROFILE: &str = "default";
+
+pub struct ProfilesAction;
+
+impl Action for ProfilesAction {
+    fn run(&self) -> Result<String, Error> {
+        let current = store::get_profile().unwrap_or(DEFAULT_PROFILE);
+        let mut names = store::list_profiles()?;
+        // a profile given with --profile for the first time has no directory yet
+        if current != DEFAULT_PROFILE && !names.iter().any(|name| name == current) {
+            names.push(current.to_string());
+            names.sort();
+        }
+        let lines: Vec<String> = std::iter::once(DEFAULT_PROFILE.to_string())
+            .chain(names)
+            .map(|name| {
+                if name == current {
+                    format!("* {}", name)
+                } else {
+                    format!("  {}", name)
+                }
+            })
+            .collect();
+        Ok(lines.join("\n"))
+    }
+}
@@ -0,0 +1,94 @@
+use crate::actions::{copy_to_clipboard, handle_matches, MatchHandlerTemplate, UnlockingAction};
+use crate::ui::input::{ask_index, ask_recovery_codes_info};
+use crate::ui::output::{show_recovery_codes, show_recovery_codes_table};
+use crate::vault::entities::{Error, RecoveryCodes};
+use crate::vault::vault_trait::Vault;
+use clap::ArgMatches;
+
+struct UseRecoveryCodeTemplate<'a> {
+    vault: &'a mut Box<dyn Vault>,
+}
+
+impl<'a> UseRecoveryCodeTemplate<'a> {
+    fn use_code(&mut self, recovery_codes: &RecoveryCodes) -> Result<Option<String>, Error> {
+        if recovery_codes.codes().is_empty() {
+            return Ok(Some(format!(
+                "No recovery codes left for '{}', generate new ones in the service",
+                recovery_codes.service()
+            )));
+        }
+        show_recovery_codes(recovery_codes);
+        match ask_index(
+            "To use one of these codes, enter a row number from the table above",
+            recovery_codes.codes().len() as i16 - 1,
+            Some("The used code is copied to clipboard and removed. Press q to exit without using a code"),
+        ) {
+            Ok(index) => {
+                copy_to_clipboard(&recovery_codes.codes()[index])?;
+                let remaining = recovery_codes.without_code(index);
+                let count = remaining.codes().len();
+                self.vault.update_recovery_codes(remaining)?;
+                Ok(Some(format!(
+                    "Recovery code copied to clipboard and marked as used, {} codes left",
+                    count
+                )))
+            }
+            Err(message) => Err(Error { message }),
+        }
+    }
+}
+
+impl<'a> MatchHandlerTemplate for UseRecoveryCodeTemplate<'a> {
+    type ItemType = RecoveryCodes;
+
+    fn pre_handle_matches(&self, matches: &Vec<Self::ItemType>) {
+        println!("Found {} recovery code entries:", matches.len());
+    }
+
+    fn handle_one_match(&mut self, the_match: Self::ItemType) -> Result<Option<String>, Error> {
+        show_recovery_codes_table(std::slice::from_ref(&the_match));
+        self.use_code(&the_match)
+    }
+
+    fn handle_many_matches(
+        &mut self,
+        matches: Vec<Self::ItemType>,
+    ) -> Result<Option<String>, Error> {
+        show_recovery_codes_table(&matches);
+        match ask_index(
+            "To see the recovery codes, enter a row number from the table above",
+            matches.len() as i16 - 1,
+            Some("Press q to exit"),
+        ) {
+            Ok(index) => self.use_code(&matches[index]),
+            Err(message) => Err(Error { message }),
+        }
+    }
+}
+
+pub struct RecoveryAction {
+    pub grep: Option<String>,
+    pub add: bool,
+}
+
+impl RecoveryAction {
+    pub fn new(matches: &ArgMatches) -> RecoveryAction {
+        RecoveryAction {
+            grep: matches.get_one::<String>("REGEXP").cloned(),
+            add: matches.get_flag("add"),
+        }
+    }
+}
+
+impl UnlockingAction for RecoveryAction {
+    fn run_with_vault(&self, vault: &mut Box<dyn Vault>) -> Result<Option<String>, Error> {
+        if self.add {
+            let recovery_codes = ask_recovery_codes_info();
+            let count = recovery_codes.codes().len();
+            vault.save_recovery_codes(&recovery_codes)?;
+            return Ok(Some(format!("Saved {} recovery codes", count)));
+        }
+        let matches = vault.find_recovery_codes(self.grep.as_deref());
+        handle_matches(matches, &mut Box::new(UseRecoveryCodeTemplate { vault }))
+    }
+}
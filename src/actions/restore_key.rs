@@ -0,0 +1,103 @@
+use crate::actions::backup_key::{configured_keyfile_path, KEY_BACKUP_NOTE_TITLE};
+use crate::actions::Action;
+use crate::store;
+use crate::ui::input::ask_password;
+use crate::vault::entities::Error;
+use crate::vault::keepass_vault::KeepassVault;
+use crate::vault::vault_trait::NoteVault;
+use clap::ArgMatches;
+use std::io::Write;
+use std::path::Path;
+use zeroize::Zeroizing;
+
+/// Reads the key from a backup made with `write_key_backup`
+pub(crate) fn read_key_backup(
+    file_path: &str,
+    passphrase: &str,
+) -> Result<Zeroizing<Vec<u8>>, Error> {
+    let backup = KeepassVault::verify_master_password(passphrase, file_path, None)?
+        .ok_or_else(|| Error::new("Wrong passphrase"))?;
+    let note = backup
+        .find_notes()
+        .into_iter()
+        .find(|note| note.title() == KEY_BACKUP_NOTE_TITLE)
+        .ok_or_else(|| Error::new(&format!("'{}' is not a key file backup", file_path)))?;
+    Ok(Zeroizing::new(hex::decode(note.content()).map_err(
+        |e| Error::new(&format!("The key file in the backup is damaged: {}", e)),
+    )?))
+}
+
+/// Restores a key file from a backup made with `backup-key`
+pub struct RestoreKeyAction {
+    pub file_path: String,
+    pub out: Option<String>,
+    pub is_totp: bool,
+    pub force: bool,
+}
+
+impl RestoreKeyAction {
+    pub fn new(matches: &ArgMatches) -> RestoreKeyAction {
+        RestoreKeyAction {
+            file_path: matches
+                .get_one::<String>("FILE_PATH")
+                .expect("required")
+                .to_string(),
+            out: matches.get_one::<String>("out").cloned(),
+            is_totp: matches.get_flag("otp"),
+            force: matches.get_flag("force"),
+        }
+    }
+}
+
+impl Action for RestoreKeyAction {
+    fn run(&self) -> Result<String, Error> {
+        let target = match &self.out {
+            Some(out) => out.clone(),
+            None => configured_keyfile_path(self.is_totp)?.ok_or_else(|| {
+                Error::new("The vault has no key file path, give the path to restore to with --out")
+            })?,
+        };
+        if !self.force && Path::new(&target).exists() {
+            return Err(Error::new(&format!(
+                "File '{}' already exists, use --force to overwrite it",
+                target
+            )));
+        }
+        let passphrase = Zeroizing::new(ask_password(
+            "Please enter the passphrase of the key backup",
+            None,
+        ));
+        let key = read_key_backup(&self.file_path, &passphrase)?;
+        store::create_private_file(&target)?.write_all(&key)?;
+        Ok(format!("Key file restored to '{}'", target))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::actions::backup_key::write_key_backup;
+    use crate::test_util::test_dir;
+
+    const KEY: [u8; 6] = [0, 1, 2, 0xfe, 0xff, b'\n'];
+
+    #[test]
+    fn key_round_trips_with_the_right_passphrase() {
+        let dir = test_dir("key-backup");
+        let path = dir.join("backup.kdbx").to_string_lossy().to_string();
+        write_key_backup(&path, &KEY, "backup passphrase").unwrap();
+        let key = read_key_backup(&path, "backup passphrase").unwrap();
+        assert_eq!(key.as_slice(), KEY);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn wrong_passphrase_is_an_error() {
+        let dir = test_dir("key-backup-wrong");
+        let path = dir.join("backup.kdbx").to_string_lossy().to_string();
+        write_key_backup(&path, &KEY, "backup passphrase").unwrap();
+        let result = read_key_backup(&path, "another passphrase");
+        assert!(result.is_err_and(|e| e.message == "Wrong passphrase"));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
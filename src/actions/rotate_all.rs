@@ -0,0 +1,247 @@
+use crate::actions::UnlockingAction;
+use crate::crypto::{self, Strength};
+use crate::ui::input::{ask_with_options, stdin_is_piped};
+use crate::ui::output::show_credentials_table;
+use crate::vault::entities::{Credential, Error};
+use crate::vault::vault_trait::Vault;
+use chrono::{Duration, Utc};
+use clap::ArgMatches;
+
+/// Rotates the credentials that match all the given criteria
+pub struct RotateAllAction {
+    pub tag: Option<String>,
+    pub older_than_days: Option<i64>,
+    pub weaker_than: Option<String>,
+    pub dry_run: bool,
+    pub yes: bool,
+}
+
+fn parse_strength(name: &str) -> Result<Strength, Error> {
+    match name {
+        "fair" => Ok(Strength::Fair),
+        "strong" => Ok(Strength::Strong),
+        _ => Err(Error::new(&format!("Unknown strength '{}'", name))),
+    }
+}
+
+impl RotateAllAction {
+    pub fn new(matches: &ArgMatches) -> RotateAllAction {
+        RotateAllAction {
+            tag: matches.get_one::<String>("tag").cloned(),
+            older_than_days: matches.get_one::<i64>("older-than").copied(),
+            weaker_than: matches.get_one::<String>("weaker-than").cloned(),
+            dry_run: matches.get_flag("dry-run"),
+            yes: matches.get_flag("yes"),
+        }
+    }
+
+    // For example "tagged 'banking', older than 365 days"
+    fn describe_criteria(&self) -> String {
+        let mut criteria = Vec::new();
+        if let Some(tag) = &self.tag {
+            criteria.push(format!("tagged '{}'", tag));
+        }
+        if let Some(days) = self.older_than_days {
+            criteria.push(format!("older than {} days", days));
+        }
+        if let Some(strength) = &self.weaker_than {
+            criteria.push(format!("weaker than {}", strength));
+        }
+        criteria.join(", ")
+    }
+
+    // Asks before rotating unless --yes is given. Without a terminal there's no one to ask.
+    fn confirm(&self, count: usize) -> Result<bool, Error> {
+        if self.yes {
+            return Ok(true);
+        }
+        if stdin_is_piped() {
+            return Err(Error::new(
+                "There's no terminal to confirm the rotation, use --yes to rotate without asking",
+            ));
+        }
+        let answer = ask_with_options(
+            &format!(
+                "Do you want to generate new passwords for these {} credentials? (yes/no)",
+                count
+            ),
+            vec!["yes", "no"],
+        );
+        Ok(answer == "yes")
+    }
+
+    fn find_candidates(&self, vault: &dyn Vault) -> Result<Vec<Credential>, Error> {
+        self.select_candidates(vault.grep(None))
+    }
+
+    fn select_candidates(&self, credentials: Vec<Credential>) -> Result<Vec<Credential>, Error> {
+        let weaker_than = self
+            .weaker_than
+            .as_deref()
+            .map(parse_strength)
+            .transpose()?;
+        let modified_before = self
+            .older_than_days
+            .map(|days| Utc::now() - Duration::days(days));
+        Ok(credentials
+            .into_iter()
+            .filter(|c| self.tag.as_ref().is_none_or(|tag| c.has_tag(tag)))
+            .filter(|c| modified_before.is_none_or(|before| *c.last_modified() < before))
+            .filter(|c| {
+                weaker_than.as_ref().is_none_or(|strength| {
+                    crypto::estimate_strength(c.password()).strength < *strength
+                })
+            })
+            .collect())
+    }
+
+    fn rotate(vault: &mut Box<dyn Vault>, credential: &Credential) -> Result<(), Error> {
+        let rotated = Credential::new(
+            Some(credential.uuid()),
+            &crypto::generate_default(),
+            credential.service(),
+            credential.username(),
+            None,
+            credential.tags(),
+            credential.notes(),
+        )
+        .with_no_username(!credential.has_username());
+        vault.update_credential(rotated)
+    }
+}
+
+impl UnlockingAction for RotateAllAction {
+    fn run_with_vault(&self, vault: &mut Box<dyn Vault>) -> Result<Option<String>, Error> {
+        let matches = self.find_candidates(vault.as_ref())?;
+        if matches.is_empty() {
+            return Ok(Some(format!(
+                "No credentials {} found",
+                self.describe_criteria()
+            )));
+        }
+        show_credentials_table(&matches, false);
+        if self.dry_run {
+            return Ok(Some(format!(
+                "Dry run: {} passwords would be rotated, nothing was changed",
+                matches.len()
+            )));
+        }
+        if !self.confirm(matches.len())? {
+            return Ok(None);
+        }
+
+        let mut failed = 0;
+        for (index, credential) in matches.iter().enumerate() {
+            let progress = format!("[{}/{}]", index + 1, matches.len());
+            match Self::rotate(vault, credential) {
+                Ok(_) => println!(
+                    "{} ok: '{}' ({})",
+                    progress,
+                    credential.service(),
+                    credential.username()
+                ),
+                Err(e) => {
+                    eprintln!(
+                        "{} failed: '{}' ({}): {}",
+                        progress,
+                        credential.service(),
+                        credential.username(),
+                        e
+                    );
+                    failed += 1;
+                }
+            }
+        }
+        Ok(Some(format!(
+            "Rotated {} passwords, {} failed. The old passwords are kept in the entry history. Remember to change the passwords in the services too.",
+            matches.len() - failed,
+            failed
+        )))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::mixed_vault;
+
+    fn action(
+        tag: Option<&str>,
+        older_than_days: Option<i64>,
+        weaker_than: Option<&str>,
+    ) -> RotateAllAction {
+        RotateAllAction {
+            tag: tag.map(String::from),
+            older_than_days,
+            weaker_than: weaker_than.map(String::from),
+            dry_run: true,
+            yes: false,
+        }
+    }
+
+    fn credential(service: &str, password: &str, age_days: i64, tags: &[&str]) -> Credential {
+        let tags: Vec<String> = tags.iter().map(|t| t.to_string()).collect();
+        Credential::new(
+            None,
+            password,
+            service,
+            "user",
+            Some(Utc::now() - Duration::days(age_days)),
+            &tags,
+            None,
+        )
+    }
+
+    fn seeded() -> Vec<Credential> {
+        vec![
+            credential("weak.com", "password", 10, &["banking"]),
+            credential("fair.com", "Tr0ub4dorHorse", 400, &[]),
+            credential("strong.com", "Xk9#mP2$vL7qR4&wZ", 500, &["banking"]),
+            credential("new.com", "Qw8!zN3@rT6^yU1*", 1, &[]),
+        ]
+    }
+
+    #[test]
+    fn notes_and_cards_are_not_candidates() {
+        let vault = mixed_vault("rotate-mixed");
+        let candidates = action(None, None, Some("strong"))
+            .find_candidates(&vault)
+            .unwrap();
+        assert_eq!(services(candidates), ["real.com"]);
+    }
+
+    fn services(candidates: Vec<Credential>) -> Vec<String> {
+        candidates.iter().map(|c| c.service().to_string()).collect()
+    }
+
+    #[test]
+    fn weaker_than_selects_the_weaker_passwords() {
+        let candidates = action(None, None, Some("strong")).select_candidates(seeded());
+        assert_eq!(services(candidates.unwrap()), ["weak.com", "fair.com"]);
+        let candidates = action(None, None, Some("fair")).select_candidates(seeded());
+        assert_eq!(services(candidates.unwrap()), ["weak.com"]);
+    }
+
+    #[test]
+    fn older_than_selects_the_old_passwords() {
+        let candidates = action(None, Some(365), None).select_candidates(seeded());
+        assert_eq!(services(candidates.unwrap()), ["fair.com", "strong.com"]);
+    }
+
+    #[test]
+    fn all_criteria_have_to_match() {
+        let candidates = action(Some("banking"), Some(365), None).select_candidates(seeded());
+        assert_eq!(services(candidates.unwrap()), ["strong.com"]);
+        let candidates =
+            action(Some("banking"), Some(365), Some("strong")).select_candidates(seeded());
+        assert!(candidates.unwrap().is_empty());
+    }
+
+    #[test]
+    fn unknown_strength_is_an_error() {
+        assert!(parse_strength("weak").is_err());
+        assert!(action(None, None, Some("medium"))
+            .select_candidates(seeded())
+            .is_err());
+    }
+}
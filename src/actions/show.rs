@@ -1,16 +1,19 @@
 use crate::actions::{
-    copy_to_clipboard, handle_matches, ItemType, MatchHandlerTemplate, UnlockingAction,
+    copy_or_print, copy_to_clipboard, exit_with, handle_matches, json_pretty_from_args, no_matches,
+    run_on_copy_command, ItemType, MatchHandlerTemplate, SearchPattern, UnlockingAction,
+    EXIT_MULTIPLE_MATCHES, EXIT_NOT_FOUND,
 };
 
-use crate::ui::input::{ask_index, ask_with_options};
+use crate::ui::input::{ask_index, ask_with_options, can_pick, pick_credential};
 use crate::ui::output::{
-    show_card, show_credentials_table, show_note, show_notes_table, show_payment_cards_table,
-    show_totp_table,
+    credential_to_json, credentials_to_json, show_card, show_credentials_table, show_note,
+    show_notes_table, show_payment_cards_table, show_totp_table,
 };
 use crate::vault::entities::{Credential, Error, Note, PaymentCard, Totp};
 use crate::vault::vault_trait::Vault;
 use clap::ArgMatches;
 use log::debug;
+use std::cmp::Ordering;
 use std::io::{self, Read, Write};
 use std::sync::mpsc;
 use std::thread;
@@ -18,39 +21,77 @@ use std::time::Duration;
 
 struct ShowCredentialsTemplate {
     verbose: bool,
+    copy_index: Option<usize>,
+    quiet: bool,
+    show_password: bool,
+}
+
+impl ShowCredentialsTemplate {
+    fn copy_password(&self, credential: &Credential) -> Result<Option<String>, Error> {
+        if self.show_password {
+            return Ok(Some(credential.password().to_string()));
+        }
+        if !copy_or_print(credential.password()) {
+            return Ok(None);
+        }
+        run_on_copy_command(credential.service());
+        Ok(Some("Password copied to clipboard!".to_string()))
+    }
+
+    fn copy_row(&self, matches: &[Credential], index: usize) -> Result<Option<String>, Error> {
+        match matches.get(index) {
+            Some(credential) => self.copy_password(credential),
+            None => Err(Error::new(&format!(
+                "Row {} is out of range, the table has rows 0 to {}",
+                index,
+                matches.len() - 1
+            ))),
+        }
+    }
 }
 
 impl MatchHandlerTemplate for ShowCredentialsTemplate {
     type ItemType = Credential;
 
     fn pre_handle_matches(&self, matches: &Vec<Self::ItemType>) {
-        println!("Found {} credentials:", matches.len());
+        if !self.quiet {
+            println!("Found {} credentials:", matches.len());
+        }
     }
 
     fn handle_one_match(&mut self, the_match: Self::ItemType) -> Result<Option<String>, Error> {
-        show_credentials_table(&vec![the_match.clone()], self.verbose);
-        copy_to_clipboard(the_match.password());
-        Ok(Some("Password copied to clipboard!".to_string()))
+        if !self.quiet {
+            show_credentials_table(std::slice::from_ref(&the_match), self.verbose);
+        }
+        match self.copy_index {
+            Some(index) => self.copy_row(std::slice::from_ref(&the_match), index),
+            None => self.copy_password(&the_match),
+        }
     }
 
     fn handle_many_matches(
         &mut self,
         matches: Vec<Self::ItemType>,
     ) -> Result<Option<String>, Error> {
-        show_credentials_table(&matches, self.verbose);
+        if !self.quiet {
+            show_credentials_table(&matches, self.verbose);
+        }
+        if let Some(index) = self.copy_index {
+            return self.copy_row(&matches, index);
+        }
 
-        match ask_index(
-            "To copy one of these passwords to clipboard, please enter a row number from the table above",
-            matches.len() as i16 - 1,
-            Some("Press q to exit without copying the password"),
-        ) {
-            Ok(index) => {
-                copy_to_clipboard(matches[index].password());
-                Ok(Some("Password copied to clipboard!".to_string()))
-            }
-            Err(message) => {
-                Err(Error { message })
-            }
+        let index = if can_pick(matches.len()) {
+            pick_credential("Select the password to copy to clipboard", &matches)
+        } else {
+            ask_index(
+                "To copy one of these passwords to clipboard, please enter a row number from the table above",
+                matches.len() as i16 - 1,
+                Some("Press q to exit without copying the password"),
+            )
+        };
+        match index {
+            Ok(index) => self.copy_password(&matches[index]),
+            Err(message) => Err(Error { message }),
         }
     }
 }
@@ -68,7 +109,7 @@ impl MatchHandlerTemplate for ShowPaymentsTemplate {
 
     fn handle_one_match(&mut self, the_match: Self::ItemType) -> Result<Option<String>, Error> {
         show_payment_cards_table(&vec![the_match.clone()], self.show_cleartext);
-        copy_to_clipboard(the_match.number());
+        copy_to_clipboard(the_match.number())?;
         match ask_with_options(
             "Do you want to see the full card details? (yes/no)",
             vec!["yes", "no"],
@@ -96,7 +137,7 @@ impl MatchHandlerTemplate for ShowPaymentsTemplate {
         ) {
             Ok(index) => {
                 show_card(&matches[index]);
-                copy_to_clipboard(matches[index].number());
+                copy_to_clipboard(matches[index].number())?;
                 Ok(Some("Card number copied to clipboard!".to_string()))
             }
             Err(message) => Err(Error { message }),
@@ -223,7 +264,7 @@ impl ShowTotpTemplate {
 
             match code {
                 Ok(code) => {
-                    copy_to_clipboard(&code.value);
+                    copy_to_clipboard(&code.value)?;
                     println!(
                         "\nCode {} (also copied to clipboard). Press q to exit.",
                         code.value
@@ -255,6 +296,100 @@ pub struct ShowAction {
     pub verbose: bool,
     pub item_type: ItemType,
     pub is_totp: bool,
+    pub exact_service: Option<String>,
+    pub username: Option<String>,
+    pub json: bool,
+    pub pretty: Option<bool>,
+    pub copy_index: Option<usize>,
+    pub quiet: bool,
+    pub show_password: bool,
+    pub tags: Vec<String>,
+    pub user: Option<String>,
+    pub field: Option<String>,
+    pub limit: Option<usize>,
+    pub page: usize,
+    pub regex: bool,
+    pub sort: Option<String>,
+    pub reverse: bool,
+}
+
+// The order that --sort puts the matching credentials in
+#[derive(Clone, Copy)]
+enum SortOrder {
+    Service,
+    Username,
+    Created,
+    Modified,
+}
+
+impl SortOrder {
+    fn new(name: &str) -> Result<SortOrder, Error> {
+        match name {
+            "service" => Ok(SortOrder::Service),
+            "username" => Ok(SortOrder::Username),
+            "created" => Ok(SortOrder::Created),
+            "modified" => Ok(SortOrder::Modified),
+            _ => Err(Error::new(&format!("Unknown sort order '{}'", name))),
+        }
+    }
+
+    // Compares ignoring case, or the oldest first. Entries that were saved without a
+    // creation time are last also in the reversed order.
+    fn sort(self, credentials: &mut [Credential], reverse: bool) {
+        let ordered = |ordering: Ordering| {
+            if reverse {
+                ordering.reverse()
+            } else {
+                ordering
+            }
+        };
+        credentials.sort_by(|a, b| match self {
+            SortOrder::Service => {
+                ordered(a.service().to_lowercase().cmp(&b.service().to_lowercase()))
+            }
+            SortOrder::Username => ordered(
+                a.username()
+                    .to_lowercase()
+                    .cmp(&b.username().to_lowercase()),
+            ),
+            SortOrder::Created => match (a.created(), b.created()) {
+                (Some(a), Some(b)) => ordered(a.cmp(b)),
+                (Some(_), None) => Ordering::Less,
+                (None, Some(_)) => Ordering::Greater,
+                (None, None) => Ordering::Equal,
+            },
+            SortOrder::Modified => ordered(a.last_modified().cmp(b.last_modified())),
+        });
+    }
+}
+
+// The field of the credentials that --field limits the search to
+#[derive(Clone, Copy)]
+enum SearchField {
+    Service,
+    Username,
+    Notes,
+}
+
+impl SearchField {
+    fn new(name: &str) -> Result<SearchField, Error> {
+        match name {
+            "service" | "url" => Ok(SearchField::Service),
+            "username" => Ok(SearchField::Username),
+            "notes" => Ok(SearchField::Notes),
+            _ => Err(Error::new(&format!("Unknown field '{}'", name))),
+        }
+    }
+
+    // Whether the field matches the search term, the same way as the search of all fields
+    fn matches(self, credential: &Credential, pattern: &SearchPattern) -> bool {
+        let value = match self {
+            SearchField::Service => credential.service(),
+            SearchField::Username => credential.username(),
+            SearchField::Notes => credential.notes().unwrap_or_default(),
+        };
+        pattern.is_match(value)
+    }
 }
 
 impl ShowAction {
@@ -264,6 +399,111 @@ impl ShowAction {
             verbose: matches.get_one::<bool>("verbose").map_or(false, |v| *v),
             item_type: ItemType::new_from_args(matches),
             is_totp: matches.get_one::<bool>("otp").map_or(false, |v| *v),
+            exact_service: matches.get_one::<String>("exact-service").cloned(),
+            username: matches.get_one::<String>("username").cloned(),
+            json: matches.get_flag("json"),
+            pretty: json_pretty_from_args(matches),
+            copy_index: matches.get_one::<usize>("copy").copied(),
+            quiet: matches.get_flag("quiet"),
+            show_password: matches.get_flag("show-password"),
+            tags: matches
+                .get_many::<String>("tag")
+                .map_or(vec![], |tags| tags.cloned().collect()),
+            user: matches.get_one::<String>("user").cloned(),
+            field: matches.get_one::<String>("field").cloned(),
+            limit: matches.get_one::<u64>("limit").map(|limit| *limit as usize),
+            page: matches
+                .get_one::<u64>("page")
+                .map_or(1, |page| *page as usize),
+            regex: matches.get_flag("regex"),
+            sort: matches.get_one::<String>("sort").cloned(),
+            reverse: matches.get_flag("reverse"),
+        }
+    }
+
+    // Whether the username has the text of --user, ignoring case
+    fn matches_user(&self, credential: &Credential) -> bool {
+        self.user.as_deref().is_none_or(|user| {
+            credential
+                .username()
+                .to_lowercase()
+                .contains(&user.to_lowercase())
+        })
+    }
+
+    // The found credentials that have all the tags of --tag and the username of --user
+    fn filter_matches(&self, found: Vec<Credential>) -> Vec<Credential> {
+        found
+            .into_iter()
+            .filter(|c| c.has_all_tags(&self.tags))
+            .filter(|c| self.matches_user(c))
+            .collect()
+    }
+
+    // The page of the matches that --limit and --page select, the total is told on stderr
+    fn limit_matches(&self, matches: Vec<Credential>) -> Result<Vec<Credential>, Error> {
+        let Some(limit) = self.limit else {
+            return Ok(matches);
+        };
+        let total = matches.len();
+        let pages = total.div_ceil(limit);
+        if total <= limit && self.page == 1 {
+            return Ok(matches);
+        }
+        let start = (self.page - 1) * limit;
+        if start >= total {
+            return Err(Error::new(&format!(
+                "Page {} is empty, the {} matches fit on {} pages",
+                self.page, total, pages
+            )));
+        }
+        let shown: Vec<Credential> = matches.into_iter().skip(start).take(limit).collect();
+        eprintln!(
+            "(showing {} of {}, page {} of {})",
+            shown.len(),
+            total,
+            self.page,
+            pages
+        );
+        Ok(shown)
+    }
+
+    fn credentials_template(&self) -> ShowCredentialsTemplate {
+        ShowCredentialsTemplate {
+            verbose: self.verbose,
+            copy_index: self.copy_index,
+            quiet: self.quiet,
+            show_password: self.show_password,
+        }
+    }
+
+    fn show_exact(&self, vault: &dyn Vault, service: &str) -> Result<Option<String>, Error> {
+        let matches: Vec<Credential> = vault
+            .grep(Some(service))
+            .into_iter()
+            .filter(|c| c.service().eq_ignore_ascii_case(service))
+            .filter(|c| self.username.as_deref().is_none_or(|u| c.username() == u))
+            .collect();
+        match matches.len() {
+            0 => exit_with(
+                EXIT_NOT_FOUND,
+                &format!("No credentials found for service '{}'", service),
+            ),
+            1 if self.json => Ok(Some(credential_to_json(
+                &matches[0],
+                self.show_password,
+                self.pretty,
+            )?)),
+            1 => self
+                .credentials_template()
+                .handle_one_match(matches[0].clone()),
+            count => exit_with(
+                EXIT_MULTIPLE_MATCHES,
+                &format!(
+                    "{} credentials found for service '{}', use --username to pick one",
+                    count, service
+                ),
+            ),
         }
     }
 }
@@ -274,22 +514,49 @@ impl UnlockingAction for ShowAction {
     }
 
     fn run_with_vault(&self, vault: &mut Box<dyn Vault>) -> Result<Option<String>, Error> {
+        if let Some(service) = &self.exact_service {
+            return self.show_exact(vault.as_ref(), service);
+        }
         match self.item_type {
             ItemType::Credential => {
-                let grep = match &self.grep {
-                    Some(grep) => grep.as_str(),
-                    None => {
-                        return Err(Error {
-                            message: "No search term REGEXP provided".to_string(),
-                        })
+                if self.grep.is_none() && self.tags.is_empty() && self.user.is_none() {
+                    return Err(Error {
+                        message: "No search term REGEXP, --tag or --user provided".to_string(),
+                    });
+                }
+                let pattern = self
+                    .grep
+                    .as_deref()
+                    .map(|grep| SearchPattern::new(grep, self.regex))
+                    .transpose()?;
+                let found = match (&self.field, &pattern) {
+                    (Some(field), Some(pattern)) => {
+                        let field = SearchField::new(field)?;
+                        vault
+                            .grep(None)
+                            .into_iter()
+                            .filter(|c| field.matches(c, pattern))
+                            .collect()
                     }
+                    (None, Some(pattern)) => pattern.find_credentials(vault.as_ref()),
+                    (_, None) => vault.grep(None),
                 };
-                handle_matches(
-                    vault.grep(Some(grep)),
-                    &mut Box::new(ShowCredentialsTemplate {
-                        verbose: self.verbose,
-                    }),
-                )
+                let mut matches = self.filter_matches(found);
+                if let Some(sort) = &self.sort {
+                    SortOrder::new(sort)?.sort(&mut matches, self.reverse);
+                }
+                let matches = self.limit_matches(matches)?;
+                if self.json {
+                    if matches.is_empty() {
+                        no_matches()?;
+                    }
+                    return Ok(Some(credentials_to_json(
+                        &matches,
+                        self.show_password,
+                        self.pretty,
+                    )?));
+                }
+                handle_matches(matches, &mut Box::new(self.credentials_template()))
             }
             ItemType::Payment => handle_matches(
                 vault.find_payments(),
@@ -310,3 +577,196 @@ impl UnlockingAction for ShowAction {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{DateTime, TimeZone, Utc};
+
+    fn show_action(args: &[&str]) -> ShowAction {
+        let matches = crate::cli().get_matches_from([&["passlane", "show"], args].concat());
+        ShowAction::new(matches.subcommand_matches("show").unwrap())
+    }
+
+    fn credential(service: &str, username: &str) -> Credential {
+        Credential::new(None, "secret", service, username, None, &[], None)
+    }
+
+    fn accounts() -> Vec<Credential> {
+        vec![
+            credential("github.com", "john@example.com"),
+            credential("github.com", "john-work@corp.com"),
+            credential("github.com", "jane@example.com"),
+        ]
+    }
+
+    fn usernames(credentials: &[Credential]) -> Vec<&str> {
+        credentials.iter().map(|c| c.username()).collect()
+    }
+
+    #[test]
+    fn user_filters_down_to_a_single_match() {
+        let matches = show_action(&["github", "--user", "WORK"]).filter_matches(accounts());
+        assert_eq!(usernames(&matches), ["john-work@corp.com"]);
+    }
+
+    #[test]
+    fn user_matches_part_of_the_username() {
+        let matches = show_action(&["github", "--user", "john"]).filter_matches(accounts());
+        assert_eq!(
+            usernames(&matches),
+            ["john@example.com", "john-work@corp.com"]
+        );
+    }
+
+    #[test]
+    fn no_user_keeps_all_matches() {
+        assert_eq!(show_action(&["github"]).filter_matches(accounts()).len(), 3);
+    }
+
+    #[test]
+    fn limit_keeps_the_first_page() {
+        let matches = show_action(&["github", "--limit", "2"])
+            .limit_matches(accounts())
+            .unwrap();
+        assert_eq!(
+            usernames(&matches),
+            ["john@example.com", "john-work@corp.com"]
+        );
+    }
+
+    #[test]
+    fn page_skips_the_earlier_pages() {
+        let matches = show_action(&["github", "--limit", "2", "--page", "2"])
+            .limit_matches(accounts())
+            .unwrap();
+        assert_eq!(usernames(&matches), ["jane@example.com"]);
+    }
+
+    #[test]
+    fn page_past_the_matches_tells_the_total() {
+        let result =
+            show_action(&["github", "--limit", "2", "--page", "3"]).limit_matches(accounts());
+        assert_eq!(
+            result.err().map(|e| e.message).as_deref(),
+            Some("Page 3 is empty, the 3 matches fit on 2 pages")
+        );
+    }
+
+    #[test]
+    fn no_limit_keeps_all_matches() {
+        let matches = show_action(&["github"]).limit_matches(accounts()).unwrap();
+        assert_eq!(matches.len(), 3);
+    }
+
+    fn day(day: u32) -> Option<DateTime<Utc>> {
+        Utc.with_ymd_and_hms(2024, 1, day, 12, 0, 0).single()
+    }
+
+    // Created and modified in a different order than the names, and one saved without a creation time
+    fn dated() -> Vec<Credential> {
+        vec![
+            Credential::new(None, "pw", "beta.com", "Carol", day(1), &[], None)
+                .with_created(day(3)),
+            Credential::new(None, "pw", "Alpha.com", "bob", day(3), &[], None).with_created(None),
+            Credential::new(None, "pw", "gamma.com", "alice", day(2), &[], None)
+                .with_created(day(1)),
+        ]
+    }
+
+    fn sorted(order: &str, reverse: bool) -> Vec<String> {
+        let mut credentials = dated();
+        SortOrder::new(order)
+            .unwrap()
+            .sort(&mut credentials, reverse);
+        credentials
+            .iter()
+            .map(|c| c.service().to_string())
+            .collect()
+    }
+
+    #[test]
+    fn sort_by_service_or_username_ignores_case() {
+        assert_eq!(
+            sorted("service", false),
+            ["Alpha.com", "beta.com", "gamma.com"]
+        );
+        assert_eq!(
+            sorted("username", false),
+            ["gamma.com", "Alpha.com", "beta.com"]
+        );
+    }
+
+    #[test]
+    fn sort_by_time_puts_the_oldest_first() {
+        assert_eq!(
+            sorted("modified", false),
+            ["beta.com", "gamma.com", "Alpha.com"]
+        );
+        assert_eq!(
+            sorted("created", false),
+            ["gamma.com", "beta.com", "Alpha.com"]
+        );
+    }
+
+    #[test]
+    fn reverse_turns_the_order_around() {
+        assert_eq!(
+            sorted("service", true),
+            ["gamma.com", "beta.com", "Alpha.com"]
+        );
+        assert_eq!(
+            sorted("modified", true),
+            ["Alpha.com", "gamma.com", "beta.com"]
+        );
+    }
+
+    #[test]
+    fn missing_creation_time_is_last_also_reversed() {
+        assert_eq!(
+            sorted("created", true),
+            ["beta.com", "gamma.com", "Alpha.com"]
+        );
+    }
+
+    #[test]
+    fn unknown_sort_order_is_an_error() {
+        assert!(SortOrder::new("password").is_err());
+    }
+
+    fn scoped(field: &str, term: &str) -> Vec<String> {
+        let field = SearchField::new(field).unwrap();
+        let pattern = SearchPattern::new(term, false).unwrap();
+        let credentials = [
+            Credential::new(
+                None,
+                "pw",
+                "mail.com",
+                "alice",
+                None,
+                &[],
+                Some("bob's account"),
+            ),
+            Credential::new(None, "pw", "bob.com", "carol", None, &[], None),
+            Credential::new(None, "pw", "shop.com", "bob", None, &[], None),
+        ];
+        credentials
+            .iter()
+            .filter(|c| field.matches(c, &pattern))
+            .map(|c| c.service().to_string())
+            .collect()
+    }
+
+    #[test]
+    fn each_field_scope_matches_only_its_field() {
+        assert_eq!(scoped("service", "bob"), ["bob.com"]);
+        assert_eq!(scoped("url", "bob"), ["bob.com"]);
+        assert_eq!(scoped("username", "bob"), ["shop.com"]);
+        assert_eq!(scoped("notes", "bob"), ["mail.com"]);
+    }
+
+    #[test]
+    fn unknown_field_is_an_error() {
+        assert!(SearchField::new("password").is_err());
+    }
+}
@@ -0,0 +1,204 @@
+use crate::actions::{json_pretty_from_args, UnlockingAction};
+use crate::crypto::{estimate_strength, Strength};
+use crate::ui::output::to_json;
+use crate::vault::entities::{Credential, Error};
+use crate::vault::vault_trait::Vault;
+use clap::ArgMatches;
+use serde::Serialize;
+use std::collections::HashMap;
+use url::Url;
+
+const MAX_BAR_WIDTH: usize = 40;
+const TOP_DOMAIN_COUNT: usize = 5;
+
+// The host of a URL service without "www.", other services as they are
+fn domain(service: &str) -> String {
+    let domain = Url::parse(service)
+        .ok()
+        .and_then(|url| url.host_str().map(String::from))
+        .unwrap_or_else(|| service.to_string())
+        .to_lowercase();
+    domain
+        .strip_prefix("www.")
+        .map(String::from)
+        .unwrap_or(domain)
+}
+
+#[derive(Serialize)]
+struct StrengthCounts {
+    weak: usize,
+    fair: usize,
+    strong: usize,
+}
+
+impl StrengthCounts {
+    fn new(credentials: &[Credential]) -> Self {
+        let strengths: Vec<Strength> = credentials
+            .iter()
+            .map(|c| estimate_strength(c.password()).strength)
+            .collect();
+        let count = |bucket: Strength| strengths.iter().filter(|s| **s == bucket).count();
+        StrengthCounts {
+            weak: count(Strength::Weak),
+            fair: count(Strength::Fair),
+            strong: count(Strength::Strong),
+        }
+    }
+
+    fn histogram(&self) -> Vec<String> {
+        let buckets = [
+            (Strength::Weak, self.weak),
+            (Strength::Fair, self.fair),
+            (Strength::Strong, self.strong),
+        ];
+        let max = buckets.iter().map(|(_, count)| *count).max().unwrap_or(0);
+        buckets
+            .iter()
+            .map(|(bucket, count)| {
+                let width = if max == 0 {
+                    0
+                } else {
+                    (count * MAX_BAR_WIDTH).div_ceil(max)
+                };
+                format!(
+                    "  {:<7} {:>5} {}",
+                    bucket.to_string(),
+                    count,
+                    "#".repeat(width)
+                )
+            })
+            .collect()
+    }
+}
+
+#[derive(Serialize)]
+struct DomainCount {
+    service: String,
+    count: usize,
+}
+
+fn top_domains(credentials: &[Credential]) -> Vec<DomainCount> {
+    let mut counts: HashMap<String, usize> = HashMap::new();
+    for credential in credentials {
+        *counts.entry(domain(credential.service())).or_insert(0) += 1;
+    }
+    let mut counts: Vec<DomainCount> = counts
+        .into_iter()
+        .map(|(service, count)| DomainCount { service, count })
+        .collect();
+    counts.sort_by(|a, b| {
+        b.count
+            .cmp(&a.count)
+            .then_with(|| a.service.cmp(&b.service))
+    });
+    counts.truncate(TOP_DOMAIN_COUNT);
+    counts
+}
+
+#[derive(Serialize)]
+struct Stats {
+    credentials: usize,
+    password_strength: StrengthCounts,
+    top_services: Vec<DomainCount>,
+    payment_cards: usize,
+    secure_notes: usize,
+    recovery_code_entries: usize,
+}
+
+impl Stats {
+    fn lines(&self) -> Vec<String> {
+        let mut lines = vec![format!("Credentials: {}", self.credentials)];
+        if self.credentials > 0 {
+            lines.push("Password strength:".to_string());
+            lines.extend(self.password_strength.histogram());
+            lines.push("Most common services:".to_string());
+            lines.extend(
+                self.top_services
+                    .iter()
+                    .map(|d| format!("  {:<30} {:>5}", d.service, d.count)),
+            );
+        }
+        lines.push(format!("Payment cards: {}", self.payment_cards));
+        lines.push(format!("Secure notes: {}", self.secure_notes));
+        lines.push(format!(
+            "Recovery code entries: {}",
+            self.recovery_code_entries
+        ));
+        lines
+    }
+}
+
+/// Prints the number of entries in the vault and the strength of the passwords
+pub struct StatsAction {
+    pub json: bool,
+    pub pretty: Option<bool>,
+}
+
+impl StatsAction {
+    pub fn new(matches: &ArgMatches) -> StatsAction {
+        StatsAction {
+            json: matches.get_flag("json"),
+            pretty: json_pretty_from_args(matches),
+        }
+    }
+}
+
+impl Stats {
+    fn new(vault: &dyn Vault) -> Stats {
+        let credentials = vault.grep(None);
+        Stats {
+            credentials: credentials.len(),
+            password_strength: StrengthCounts::new(&credentials),
+            top_services: top_domains(&credentials),
+            payment_cards: vault.find_payments().len(),
+            secure_notes: vault.find_notes().len(),
+            recovery_code_entries: vault.find_recovery_codes(None).len(),
+        }
+    }
+}
+
+impl UnlockingAction for StatsAction {
+    fn run_with_vault(&self, vault: &mut Box<dyn Vault>) -> Result<Option<String>, Error> {
+        let stats = Stats::new(vault.as_ref());
+        if self.json {
+            return Ok(Some(to_json(&stats, self.pretty)?));
+        }
+        Ok(Some(stats.lines().join("\n")))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::mixed_vault;
+    use crate::vault::vault_trait::PasswordVault;
+    use serde_json::Value;
+
+    fn credential(service: &str, password: &str) -> Credential {
+        Credential::new(None, password, service, "user", None, &[], None)
+    }
+
+    #[test]
+    fn stats_json_counts_the_credentials_apart_from_notes_and_cards() {
+        let mut vault = mixed_vault("stats-mixed");
+        vault
+            .save_credentials(&vec![
+                credential("https://www.example.com/login", "Xk9#mP2$vL7qR4&wZ"),
+                credential("https://example.com", "Tr0ub4dorHorse"),
+                credential("example.com", "Qw8!zN3@rT6^yU1*"),
+            ])
+            .unwrap();
+        let json = to_json(&Stats::new(&vault), Some(false)).unwrap();
+        let parsed: Value = serde_json::from_str(&json).unwrap();
+        assert_eq!(parsed["credentials"], 4);
+        assert_eq!(parsed["password_strength"]["weak"], 1);
+        assert_eq!(parsed["password_strength"]["fair"], 1);
+        assert_eq!(parsed["password_strength"]["strong"], 2);
+        assert_eq!(parsed["top_services"][0]["service"], "example.com");
+        assert_eq!(parsed["top_services"][0]["count"], 3);
+        assert_eq!(parsed["payment_cards"], 1);
+        assert_eq!(parsed["secure_notes"], 1);
+        assert!(!json.contains("(no service)"), "{}", json);
+        assert!(!json.contains("Xk9#"));
+    }
+}
@@ -0,0 +1,92 @@
+use crate::actions::{json_pretty_from_args, Action};
+use crate::keychain;
+use crate::store;
+use crate::ui::output::to_json;
+use crate::vault::entities::Error;
+use clap::ArgMatches;
+use serde::Serialize;
+use std::path::Path;
+
+fn lock_state(unlocked: bool) -> &'static str {
+    if unlocked {
+        "unlocked"
+    } else {
+        "locked"
+    }
+}
+
+fn file_state(path: &str) -> String {
+    if Path::new(path).exists() {
+        path.to_string()
+    } else {
+        format!("{} (not created yet)", path)
+    }
+}
+
+#[derive(Serialize)]
+struct VaultStatus {
+    path: String,
+    exists: bool,
+    unlocked: bool,
+}
+
+impl VaultStatus {
+    fn new(path: String, unlocked: bool) -> Self {
+        VaultStatus {
+            exists: Path::new(&path).exists(),
+            path,
+            unlocked,
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct Status {
+    profile: Option<&'static str>,
+    vault: VaultStatus,
+    keyfile: Option<String>,
+    totp_vault: VaultStatus,
+}
+
+pub struct StatusAction {
+    pub json: bool,
+    pub pretty: Option<bool>,
+}
+
+impl StatusAction {
+    pub fn new(matches: &ArgMatches) -> StatusAction {
+        StatusAction {
+            json: matches.get_flag("json"),
+            pretty: json_pretty_from_args(matches),
+        }
+    }
+}
+
+impl Action for StatusAction {
+    fn run(&self) -> Result<String, Error> {
+        let status = Status {
+            profile: store::get_profile(),
+            vault: VaultStatus::new(
+                store::get_vault_path()?,
+                keychain::get_master_password().is_ok(),
+            ),
+            keyfile: store::get_keyfile_path()?,
+            totp_vault: VaultStatus::new(
+                store::get_totp_vault_path()?,
+                keychain::get_totp_master_password().is_ok(),
+            ),
+        };
+        if self.json {
+            return to_json(&status, self.pretty);
+        }
+        let lines = [
+            format!("Profile: {}", status.profile.unwrap_or("default")),
+            format!("Vault: {}", file_state(&status.vault.path)),
+            format!("Keyfile: {}", status.keyfile.as_deref().unwrap_or("none")),
+            format!("Vault is {}", lock_state(status.vault.unlocked)),
+            format!("TOTP vault: {}", file_state(&status.totp_vault.path)),
+            format!("TOTP vault is {}", lock_state(status.totp_vault.unlocked)),
+        ];
+        Ok(lines.join("\n"))
+    }
+}
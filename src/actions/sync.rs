@@ -0,0 +1,228 @@
+use crate::actions::Action;
+use crate::config;
+use crate::remote::{CurlStore, RemoteStore};
+use crate::store;
+use crate::vault::entities::Error;
+use chrono::DateTime;
+use clap::ArgMatches;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+
+#[derive(Debug, PartialEq)]
+enum Direction {
+    Upload,
+    Download,
+    UpToDate,
+}
+
+// The modification time of the local vault in milliseconds, None if there's no vault yet
+fn local_modified(path: &Path) -> Result<Option<u128>, Error> {
+    if !path.exists() {
+        return Ok(None);
+    }
+    Ok(Some(
+        fs::metadata(path)?
+            .modified()?
+            .duration_since(UNIX_EPOCH)?
+            .as_millis(),
+    ))
+}
+
+// Which copy is newer when both have changed, for the conflict message
+fn newer_copy(local: u128, remote: &str) -> &'static str {
+    match DateTime::parse_from_rfc2822(remote) {
+        Ok(remote) if remote.timestamp_millis() as u128 > local => "the remote one",
+        Ok(_) => "the local one",
+        Err(_) => "unknown",
+    }
+}
+
+// The copy that changed since the last sync replaces the other one. When both have changed,
+// or they have not been synced before, nothing is replaced because changes would be lost.
+fn decide(
+    local: Option<u128>,
+    remote: Option<&str>,
+    last_sync: Option<(u128, String)>,
+) -> Result<Direction, String> {
+    let (local, remote) = match (local, remote) {
+        (None, None) => return Err("There is no vault to sync yet".to_string()),
+        (Some(_), None) => return Ok(Direction::Upload),
+        (None, Some(_)) => return Ok(Direction::Download),
+        (Some(local), Some(remote)) => (local, remote),
+    };
+    match last_sync {
+        Some((synced_local, synced_remote)) => {
+            match (local != synced_local, remote != synced_remote) {
+                (false, false) => Ok(Direction::UpToDate),
+                (true, false) => Ok(Direction::Upload),
+                (false, true) => Ok(Direction::Download),
+                (true, true) => Err(format!(
+                    "Both the local and the remote vault have changed since the last sync, {} is newer",
+                    newer_copy(local, remote)
+                )),
+            }
+        }
+        None => Err(format!(
+            "The local and the remote vault have not been synced before, {} is newer",
+            newer_copy(local, remote)
+        )),
+    }
+}
+
+/// Copies the vault file to or from the WebDAV or S3 server in the `[sync]` settings
+pub struct SyncAction {
+    pub upload: bool,
+    pub download: bool,
+}
+
+impl SyncAction {
+    pub fn new(matches: &ArgMatches) -> SyncAction {
+        SyncAction {
+            upload: matches.get_flag("upload"),
+            download: matches.get_flag("download"),
+        }
+    }
+
+    fn direction(
+        &self,
+        local: Option<u128>,
+        remote: Option<&str>,
+        url: &str,
+    ) -> Result<Direction, Error> {
+        if self.upload {
+            return Ok(Direction::Upload);
+        }
+        if self.download {
+            return Ok(Direction::Download);
+        }
+        decide(local, remote, store::get_sync_state(url)).map_err(|message| {
+            Error::new(&format!(
+                "{}. Nothing was synced, use --upload to replace the remote vault or --download to replace the local one.",
+                message
+            ))
+        })
+    }
+}
+
+// Downloads to a file next to the vault first, so that a failed download doesn't leave a broken vault
+fn download(remote: &dyn RemoteStore, vault_path: &Path) -> Result<(), Error> {
+    let mut partial = vault_path.as_os_str().to_owned();
+    partial.push(".download");
+    let partial = PathBuf::from(partial);
+    store::create_private_file(&partial.to_string_lossy())?;
+    let result = remote
+        .download(&partial)
+        .and_then(|_| Ok(fs::rename(&partial, vault_path)?));
+    if result.is_err() {
+        let _ = fs::remove_file(&partial);
+    }
+    result
+}
+
+impl Action for SyncAction {
+    fn run(&self) -> Result<String, Error> {
+        let Some(sync_config) = config::get().sync.as_ref() else {
+            return Err(Error::new(
+                "Syncing is not set up, add a [sync] table with the url of the remote vault to config.toml",
+            ));
+        };
+        let remote = CurlStore::new(sync_config)?;
+        let vault_path = PathBuf::from(store::get_vault_path()?);
+        let url = remote.location();
+        let remote_modified = remote.last_modified()?;
+        let direction = self.direction(
+            local_modified(&vault_path)?,
+            remote_modified.as_deref(),
+            url,
+        )?;
+        let message = match direction {
+            Direction::UpToDate => return Ok(format!("The vault is up to date with {}", url)),
+            Direction::Upload => {
+                if !vault_path.exists() {
+                    return Err(Error::new("There is no local vault to upload"));
+                }
+                remote.upload(&vault_path)?;
+                format!("Uploaded {} to {}", vault_path.display(), url)
+            }
+            Direction::Download => {
+                if remote_modified.is_none() {
+                    return Err(Error::new(&format!("There is no vault at {}", url)));
+                }
+                download(&remote, &vault_path)?;
+                format!("Downloaded {} to {}", url, vault_path.display())
+            }
+        };
+        // the state is read back so that the next sync sees exactly what the server and the file system say
+        match (local_modified(&vault_path)?, remote.last_modified()?) {
+            (Some(local), Some(remote_modified)) => {
+                store::save_sync_state(url, local, &remote_modified)?
+            }
+            _ => eprintln!("Warning: failed to save the state of the sync"),
+        }
+        Ok(message)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const REMOTE: &str = "Tue, 14 Oct 2025 10:00:00 GMT";
+    const REMOTE_MILLIS: u128 = 1_760_436_000_000;
+
+    fn synced() -> Option<(u128, String)> {
+        Some((REMOTE_MILLIS, REMOTE.to_string()))
+    }
+
+    #[test]
+    fn unchanged_copies_are_up_to_date() {
+        assert_eq!(
+            decide(Some(REMOTE_MILLIS), Some(REMOTE), synced()),
+            Ok(Direction::UpToDate)
+        );
+    }
+
+    #[test]
+    fn changed_copy_replaces_the_other() {
+        assert_eq!(
+            decide(Some(REMOTE_MILLIS + 1), Some(REMOTE), synced()),
+            Ok(Direction::Upload)
+        );
+        let newer_remote = "Tue, 14 Oct 2025 11:00:00 GMT";
+        assert_eq!(
+            decide(Some(REMOTE_MILLIS), Some(newer_remote), synced()),
+            Ok(Direction::Download)
+        );
+    }
+
+    #[test]
+    fn both_changed_is_a_conflict_that_names_the_newer_copy() {
+        let newer_remote = "Tue, 14 Oct 2025 11:00:00 GMT";
+        assert_eq!(
+            decide(Some(REMOTE_MILLIS + 1), Some(newer_remote), synced()),
+            Err("Both the local and the remote vault have changed since the last sync, the remote one is newer".to_string())
+        );
+        let older_remote = "Tue, 14 Oct 2025 09:00:00 GMT";
+        assert_eq!(
+            decide(Some(REMOTE_MILLIS + 1), Some(older_remote), synced()),
+            Err("Both the local and the remote vault have changed since the last sync, the local one is newer".to_string())
+        );
+    }
+
+    #[test]
+    fn copies_never_synced_are_a_conflict() {
+        let newer_remote = "Tue, 14 Oct 2025 11:00:00 GMT";
+        assert_eq!(
+            decide(Some(REMOTE_MILLIS), Some(newer_remote), None),
+            Err("The local and the remote vault have not been synced before, the remote one is newer".to_string())
+        );
+    }
+
+    #[test]
+    fn missing_copy_is_filled_from_the_other() {
+        assert_eq!(decide(Some(1), None, None), Ok(Direction::Upload));
+        assert_eq!(decide(None, Some(REMOTE), None), Ok(Direction::Download));
+        assert!(decide(None, None, synced()).is_err());
+    }
+}
@@ -1,16 +1,19 @@
 use clap::ArgMatches;
-use crate::actions::{Action, unlock, unlock_totp_vault};
+use crate::actions::{Action, unix_time, unlock, unlock_totp_vault};
 use crate::keychain;
+use crate::store;
 use crate::vault::entities::Error;
 
 pub struct UnlockAction {
     pub totp: bool,
+    pub minutes: Option<u64>,
 }
 
 impl UnlockAction {
     pub fn new(matches: &ArgMatches) -> UnlockAction {
         UnlockAction {
-            totp: matches.get_one::<bool>("otp").map_or(false, |v| *v),
+            totp: matches.get_flag("otp"),
+            minutes: matches.get_one::<u64>("for").copied(),
         }
     }
 }
@@ -24,6 +27,15 @@ impl Action for UnlockAction {
             let vault = unlock()?;
             keychain::save_master_password(&vault.get_master_password())?;
         }
-        Ok("Vault unlocked".to_string())
+        match self.minutes {
+            Some(minutes) => {
+                store::save_unlock_expiry(self.totp, unix_time() + minutes * 60)?;
+                Ok(format!("Vault unlocked for {} minutes", minutes))
+            }
+            None => {
+                store::clear_unlock_expiry(self.totp)?;
+                Ok("Vault unlocked".to_string())
+            }
+        }
     }
 }
@@ -0,0 +1,70 @@
+#[cfg(feature = "clipboard")]
+use ::clipboard::{ClipboardContext, ClipboardProvider};
+use anyhow::anyhow;
+#[cfg(feature = "clipboard")]
+use std::sync::{Mutex, OnceLock};
+
+/// The clipboard of the desktop session. It's opened once per run and shared, because
+/// opening it is slow on some platforms and opening it again while copying can race.
+#[cfg(feature = "clipboard")]
+pub struct Clipboard {
+    context: Mutex<ClipboardContext>,
+}
+
+#[cfg(feature = "clipboard")]
+static CLIPBOARD: OnceLock<Result<Clipboard, String>> = OnceLock::new();
+
+#[cfg(feature = "clipboard")]
+impl Clipboard {
+    pub fn get_contents(&self) -> anyhow::Result<String> {
+        self.context
+            .lock()
+            .unwrap()
+            .get_contents()
+            .map_err(|e| anyhow!("Failed to read the clipboard: {}", e))
+    }
+
+    pub fn set_contents(&self, value: &str) -> anyhow::Result<()> {
+        self.context
+            .lock()
+            .unwrap()
+            .set_contents(String::from(value))
+            .map_err(|e| anyhow!("Failed to copy to clipboard: {}", e))
+    }
+}
+
+/// The shared clipboard, opened on the first call. A clipboard that could not be opened
+/// is not retried, the later calls return the same error.
+#[cfg(feature = "clipboard")]
+pub fn get() -> anyhow::Result<&'static Clipboard> {
+    CLIPBOARD
+        .get_or_init(|| {
+            ClipboardProvider::new()
+                .map(|context| Clipboard {
+                    context: Mutex::new(context),
+                })
+                .map_err(|e| format!("Clipboard is not available: {}", e))
+        })
+        .as_ref()
+        .map_err(|message| anyhow!("{}", message))
+}
+
+#[cfg(feature = "clipboard")]
+pub fn get_contents() -> anyhow::Result<String> {
+    get()?.get_contents()
+}
+
+#[cfg(feature = "clipboard")]
+pub fn set(value: &str) -> anyhow::Result<()> {
+    get()?.set_contents(value)
+}
+
+#[cfg(not(feature = "clipboard"))]
+pub fn get_contents() -> anyhow::Result<String> {
+    Err(anyhow!("Passlane was built without clipboard support"))
+}
+
+#[cfg(not(feature = "clipboard"))]
+pub fn set(_: &str) -> anyhow::Result<()> {
+    Err(anyhow!("Passlane was built without clipboard support"))
+}
@@ -0,0 +1,62 @@
+use crate::crypto::{PasswordPolicy, PasswordSpec};
+use crate::remote::SyncConfig;
+use crate::store;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::sync::OnceLock;
+
+const KNOWN_KEYS: [&str; 6] = [
+    "password_length",
+    "clipboard_timeout",
+    "keychain_default",
+    "password_policy",
+    "presets",
+    "sync",
+];
+
+/// The defaults from `~/.passlane/config.toml`. Command line options override these.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    /// Length of the generated passwords
+    pub password_length: Option<usize>,
+    /// Seconds after which a copied value is cleared from the clipboard
+    pub clipboard_timeout: Option<u64>,
+    /// Save the master password to the keychain when it's asked, as if `unlock` was run
+    pub keychain_default: bool,
+    /// The rules for passwords that are saved from the clipboard or stdin
+    pub password_policy: PasswordPolicy,
+    /// Named password specs for `--preset`, in addition to the built-in ones
+    pub presets: HashMap<String, PasswordSpec>,
+    /// The server that `sync` copies the vault file to and from
+    pub sync: Option<SyncConfig>,
+}
+
+static CONFIG: OnceLock<Config> = OnceLock::new();
+
+fn parse(content: &str) -> Result<Config, toml::de::Error> {
+    let table: toml::Table = toml::from_str(content)?;
+    for key in table.keys().filter(|k| !KNOWN_KEYS.contains(&k.as_str())) {
+        eprintln!("Warning: unknown setting '{}' in config.toml", key);
+    }
+    table.try_into()
+}
+
+fn load() -> Config {
+    match store::read_config() {
+        Ok(Some(content)) => parse(&content).unwrap_or_else(|e| {
+            eprintln!("Warning: ignoring config.toml, failed to parse it: {}", e);
+            Config::default()
+        }),
+        Ok(None) => Config::default(),
+        Err(e) => {
+            eprintln!("Warning: ignoring config.toml, {}", e);
+            Config::default()
+        }
+    }
+}
+
+/// The config, read from the file on first use
+pub fn get() -> &'static Config {
+    CONFIG.get_or_init(load)
+}
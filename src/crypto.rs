@@ -1,47 +1,567 @@
+use crate::config;
+use crate::vault::entities::{Credential, Error};
+use rand::seq::SliceRandom;
 use rand::thread_rng;
 use rand::Rng;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::fmt::{self, Display};
 
 const LOW_CASE: &str = "abcdefghijklmnopqrstuvxyz";
 const UP_CASE: &str = "ABCDEFGHIJKLMNOPQRSTUVXYZ";
 const NUMBERS: &str = "0123456789";
 pub const SPECIAL: &str = "£$&()*+[]@#^-_!?:;,.{}<>~%/\\|\"'`´^¨=§";
+/// The characters that are left out with `exclude_ambiguous`. In many fonts `I`, `l`, `1` and `|` look alike, and so do
+/// `O`, `o` and `0`, and the quotes and accents are hard to tell apart when a password is read or typed by hand.
+pub const AMBIGUOUS_CHARACTERS: &str = "Il1|O0o`'\"´¨";
 
-pub fn generate() -> String {
-    let mut password = "".to_string();
+const DEFAULT_LENGTH: usize = 15;
+// The letters of the syllables of pronounceable passwords, leaving out the ones that are easy to mishear
+const CONSONANTS: &str = "bdfghklmnprstvz";
+const VOWELS: &str = "aeiou";
+// Pronounceable passwords are split to groups of this many letters
+const SYLLABLE_GROUP_LENGTH: usize = 6;
+const PRONOUNCEABLE_DIGIT_COUNT: usize = 2;
+const MIN_PRONOUNCEABLE_ENTROPY_BITS: f64 = 36.0;
+// https://www.eff.org/dice
+const WORDLIST: &str = include_str!("eff_large_wordlist.txt");
+const COMMON_PASSWORDS: &str = include_str!("common_passwords.txt");
 
-    for _ in 0..=14 {
-        let char_group = random_index(4);
-        password = match char_group {
-            0 => append(&password, &LOW_CASE.to_string()),
-            1 => append(&password, &UP_CASE.to_string()),
-            2 => append(&password, &NUMBERS.to_string()),
-            3 => append(&password, &SPECIAL.to_string()),
-            _ => password,
+/// The character groups and length of a random password. Presets in the config use the same keys.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default)]
+pub struct PasswordSpec {
+    pub length: usize,
+    pub include_lowercase: bool,
+    pub include_uppercase: bool,
+    pub include_digits: bool,
+    pub include_symbols: bool,
+    pub exclude_ambiguous: bool,
+}
+
+impl Default for PasswordSpec {
+    fn default() -> Self {
+        PasswordSpec {
+            length: DEFAULT_LENGTH,
+            include_lowercase: true,
+            include_uppercase: true,
+            include_digits: true,
+            include_symbols: true,
+            exclude_ambiguous: false,
         }
     }
-    password
 }
 
-pub fn validate_password(value: &String) -> bool {
-    value.len() >= 15
-        && value.chars().any(|c| LOW_CASE.contains(c))
-        && value.chars().any(|c| UP_CASE.contains(c))
-        && value.chars().any(|c| NUMBERS.contains(c))
-        && value.chars().any(|c| SPECIAL.contains(c))
+const fn preset_spec(length: usize, letters: bool, symbols: bool) -> PasswordSpec {
+    PasswordSpec {
+        length,
+        include_lowercase: letters,
+        include_uppercase: letters,
+        include_digits: true,
+        include_symbols: symbols,
+        exclude_ambiguous: false,
+    }
 }
 
-fn random_index(range: usize) -> usize {
+// The presets for common site policies, presets of the config with the same names replace these
+const BUILTIN_PRESETS: [(&str, PasswordSpec); 5] = [
+    ("pin4", preset_spec(4, false, false)),
+    ("pin6", preset_spec(6, false, false)),
+    ("wifi", preset_spec(20, true, false)),
+    ("strong", preset_spec(24, true, true)),
+    ("alnum16", preset_spec(16, true, false)),
+];
+
+/// The spec of the named preset, looked up first from the presets of the config and then from the built-in ones.
+pub fn find_preset(
+    name: &str,
+    custom_presets: &HashMap<String, PasswordSpec>,
+) -> Result<PasswordSpec, Error> {
+    if let Some(spec) = custom_presets.get(name) {
+        return Ok(spec.clone());
+    }
+    if let Some((_, spec)) = BUILTIN_PRESETS.iter().find(|(builtin, _)| *builtin == name) {
+        return Ok(spec.clone());
+    }
+    let mut names: Vec<&str> = BUILTIN_PRESETS
+        .iter()
+        .map(|(builtin, _)| *builtin)
+        .collect();
+    names.extend(custom_presets.keys().map(String::as_str));
+    names.sort();
+    names.dedup();
+    Err(Error::new(&format!(
+        "Unknown preset '{}'. The presets are: {}",
+        name,
+        names.join(", ")
+    )))
+}
+
+// The included character groups, without the ambiguous characters if they are excluded
+fn character_groups(spec: &PasswordSpec) -> Vec<String> {
+    [
+        (spec.include_lowercase, LOW_CASE),
+        (spec.include_uppercase, UP_CASE),
+        (spec.include_digits, NUMBERS),
+        (spec.include_symbols, SPECIAL),
+    ]
+    .iter()
+    .filter(|(included, _)| *included)
+    .map(|(_, group)| {
+        group
+            .chars()
+            .filter(|c| !spec.exclude_ambiguous || !AMBIGUOUS_CHARACTERS.contains(*c))
+            .collect()
+    })
+    .collect()
+}
+
+/// The entropy of a random password of the spec in bits, counted from the number of characters it's picked from.
+pub fn random_entropy_bits(spec: &PasswordSpec) -> f64 {
+    let pool_size: usize = character_groups(spec)
+        .iter()
+        .map(|group| group.chars().count())
+        .sum();
+    spec.length as f64 * (pool_size as f64).log2()
+}
+
+/// Generates a password that has at least one character from each of the included character groups.
+pub fn generate(spec: &PasswordSpec) -> Result<String, Error> {
+    let groups = character_groups(spec);
+    if groups.is_empty() {
+        return Err(Error::new("At least one character group must be included"));
+    }
+    if spec.length < groups.len() {
+        return Err(Error::new(&format!(
+            "The password length must be at least {} to include all the selected character groups",
+            groups.len()
+        )));
+    }
     let mut rng = thread_rng();
-    rng.gen_range(0..range.try_into().unwrap())
+    let mut password: Vec<char> = groups.iter().map(|g| random_char(g)).collect();
+    while password.len() < spec.length {
+        password.push(random_char(&groups[random_index(groups.len())]));
+    }
+    password.shuffle(&mut rng);
+    Ok(password.into_iter().collect())
+}
+
+/// Generates a diceware style passphrase from the EFF long wordlist.
+pub fn generate_passphrase(word_count: usize, separator: &str) -> Result<String, Error> {
+    if word_count == 0 {
+        return Err(Error::new("The passphrase must have at least one word"));
+    }
+    // each line has the dice roll and the word separated by a tab
+    let words: Vec<&str> = WORDLIST
+        .lines()
+        .filter_map(|line| line.split('\t').nth(1))
+        .collect();
+    let mut rng = thread_rng();
+    let phrase: Vec<&str> = (0..word_count)
+        .map(|_| *words.choose(&mut rng).expect("The wordlist is not empty"))
+        .collect();
+    Ok(phrase.join(separator))
+}
+
+// The entropy of a pronounceable password of this many letters, where consonants and vowels alternate
+fn pronounceable_entropy_bits(length: usize, with_digits: bool) -> f64 {
+    let consonants = length.div_ceil(2) as f64;
+    let vowels = (length / 2) as f64;
+    let digits = if with_digits {
+        PRONOUNCEABLE_DIGIT_COUNT as f64
+    } else {
+        0.0
+    };
+    consonants * (CONSONANTS.len() as f64).log2()
+        + vowels * (VOWELS.len() as f64).log2()
+        + digits * (NUMBERS.len() as f64).log2()
+}
+
+/// Generates a password that can be read aloud, like `tomabi-kuvelo`, with the given number of letters
+/// in syllables of a consonant and a vowel. Two digits are added to the end with `with_digits`.
+pub fn generate_pronounceable(length: usize, with_digits: bool) -> Result<String, Error> {
+    if pronounceable_entropy_bits(length, with_digits) < MIN_PRONOUNCEABLE_ENTROPY_BITS {
+        let min_length = (1..)
+            .find(|l| pronounceable_entropy_bits(*l, with_digits) >= MIN_PRONOUNCEABLE_ENTROPY_BITS)
+            .expect("Long passwords have enough entropy");
+        return Err(Error::new(&format!(
+            "A pronounceable password needs at least {} letters to be hard enough to guess",
+            min_length
+        )));
+    }
+    let letters: Vec<char> = (0..length)
+        .map(|i| random_char(if i % 2 == 0 { CONSONANTS } else { VOWELS }))
+        .collect();
+    let mut password = letters
+        .chunks(SYLLABLE_GROUP_LENGTH)
+        .map(|group| group.iter().collect::<String>())
+        .collect::<Vec<String>>()
+        .join("-");
+    if with_digits {
+        password.extend((0..PRONOUNCEABLE_DIGIT_COUNT).map(|_| random_char(NUMBERS)));
+    }
+    Ok(password)
+}
+
+pub enum PasswordGenerator {
+    Random(PasswordSpec),
+    Passphrase {
+        word_count: usize,
+        separator: String,
+    },
+    Pronounceable {
+        length: usize,
+        with_digits: bool,
+    },
+    /// A random password of a named preset, the length overrides the length of the preset
+    Preset {
+        name: String,
+        length: Option<usize>,
+    },
+}
+
+impl PasswordGenerator {
+    pub fn generate(&self) -> Result<String, Error> {
+        match self {
+            PasswordGenerator::Random(spec) => generate(spec),
+            PasswordGenerator::Passphrase {
+                word_count,
+                separator,
+            } => generate_passphrase(*word_count, separator),
+            PasswordGenerator::Pronounceable {
+                length,
+                with_digits,
+            } => generate_pronounceable(*length, *with_digits),
+            PasswordGenerator::Preset { name, length } => {
+                let spec = find_preset(name, &config::get().presets)?;
+                generate(&PasswordSpec {
+                    length: length.unwrap_or(spec.length),
+                    ..spec
+                })
+            }
+        }
+    }
+}
+
+pub fn generate_default() -> String {
+    generate(&PasswordSpec::default()).expect("The default password spec is valid")
+}
+
+/// The rules that a password has to pass in `validate_password`
+#[derive(Debug, Deserialize)]
+#[serde(default)]
+pub struct PasswordPolicy {
+    pub min_length: usize,
+    pub require_lowercase: bool,
+    pub require_uppercase: bool,
+    pub require_digits: bool,
+    pub require_symbols: bool,
+    /// The minimum Shannon entropy of the whole password in bits
+    pub min_entropy_bits: f64,
+}
+
+impl Default for PasswordPolicy {
+    fn default() -> Self {
+        PasswordPolicy {
+            min_length: DEFAULT_LENGTH,
+            require_lowercase: true,
+            require_uppercase: true,
+            require_digits: true,
+            require_symbols: true,
+            min_entropy_bits: 0.0,
+        }
+    }
+}
+
+/// Checks the password against the policy, returns the rule that failed
+pub fn validate_password(value: &str, policy: &PasswordPolicy) -> Result<(), String> {
+    if value.chars().count() < policy.min_length {
+        return Err(format!(
+            "it is shorter than {} characters",
+            policy.min_length
+        ));
+    }
+    let groups = [
+        (policy.require_lowercase, LOW_CASE, "lowercase letters"),
+        (policy.require_uppercase, UP_CASE, "uppercase letters"),
+        (policy.require_digits, NUMBERS, "digits"),
+        (policy.require_symbols, SPECIAL, "special characters"),
+    ];
+    for (required, group, name) in groups {
+        if required && !value.chars().any(|c| group.contains(c)) {
+            return Err(format!("it has no {}", name));
+        }
+    }
+    let entropy_bits = shannon_entropy_bits(value);
+    if entropy_bits < policy.min_entropy_bits {
+        return Err(format!(
+            "its entropy is {:.0} bits, less than the required {:.0} bits",
+            entropy_bits, policy.min_entropy_bits
+        ));
+    }
+    Ok(())
+}
+
+const MIN_STRONG_LENGTH: usize = 12;
+const MIN_STRONG_CHAR_GROUPS: usize = 3;
+
+const MIN_VERY_STRONG_LENGTH: usize = 16;
+
+fn char_group_count(password: &str) -> usize {
+    [LOW_CASE, UP_CASE, NUMBERS, SPECIAL]
+        .iter()
+        .filter(|group| password.chars().any(|c| group.contains(c)))
+        .count()
+}
+
+/// Weak passwords are short or use less than three of the character groups
+pub fn is_weak(password: &str) -> bool {
+    password.chars().count() < MIN_STRONG_LENGTH
+        || char_group_count(password) < MIN_STRONG_CHAR_GROUPS
+}
+
+#[derive(Debug, PartialEq, PartialOrd)]
+pub enum Strength {
+    Weak,
+    Fair,
+    Strong,
+}
+
+impl Display for Strength {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Strength::Weak => write!(f, "weak"),
+            Strength::Fair => write!(f, "fair"),
+            Strength::Strong => write!(f, "strong"),
+        }
+    }
+}
+
+pub struct StrengthReport {
+    pub strength: Strength,
+    /// Shannon entropy of the characters times the length
+    pub entropy_bits: f64,
 }
 
-fn append(to: &String, charset: &String) -> String {
-    let character = charset.chars().nth(random_index(charset.len() - 1));
-    let character = match character {
-        Some(c) => c,
-        None => '-',
+impl Display for StrengthReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} ({:.0} bits)", self.strength, self.entropy_bits)
+    }
+}
+
+fn shannon_entropy_bits(password: &str) -> f64 {
+    let mut counts: HashMap<char, usize> = HashMap::new();
+    for c in password.chars() {
+        *counts.entry(c).or_insert(0) += 1;
+    }
+    let length = password.chars().count() as f64;
+    let bits_per_char: f64 = counts
+        .values()
+        .map(|&count| {
+            let p = count as f64 / length;
+            -p * p.log2()
+        })
+        .sum();
+    bits_per_char * length
+}
+
+/// True when the password is on the built-in list of common passwords
+pub(crate) fn is_common(password: &str) -> bool {
+    COMMON_PASSWORDS
+        .lines()
+        .any(|common| common.eq_ignore_ascii_case(password))
+}
+
+/// Rates the password by its length, character groups and the list of common passwords
+pub fn estimate_strength(password: &str) -> StrengthReport {
+    let strength = if is_common(password) || is_weak(password) {
+        Strength::Weak
+    } else if password.chars().count() < MIN_VERY_STRONG_LENGTH && char_group_count(password) < 4 {
+        Strength::Fair
+    } else {
+        Strength::Strong
     };
-    let mut result = String::from(to);
-    result.push(character);
-    result
+    StrengthReport {
+        strength,
+        entropy_bits: shannon_entropy_bits(password),
+    }
+}
+
+/// Returns the credentials whose password is also used by another credential
+pub fn find_reused(creds: &[Credential]) -> Vec<Credential> {
+    let mut counts: HashMap<&str, usize> = HashMap::new();
+    for cred in creds {
+        *counts.entry(cred.password()).or_insert(0) += 1;
+    }
+    creds
+        .iter()
+        .filter(|c| counts[c.password()] > 1)
+        .cloned()
+        .collect()
+}
+
+fn random_index(range: usize) -> usize {
+    let mut rng = thread_rng();
+    rng.gen_range(0..range)
+}
+
+fn random_char(charset: &str) -> char {
+    let chars: Vec<char> = charset.chars().collect();
+    chars[random_index(chars.len())]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn password_has_the_given_length() {
+        let spec = PasswordSpec {
+            length: 64,
+            ..PasswordSpec::default()
+        };
+        assert_eq!(generate(&spec).unwrap().chars().count(), 64);
+    }
+
+    #[test]
+    fn spec_without_character_groups_is_an_error() {
+        let spec = PasswordSpec {
+            include_lowercase: false,
+            include_uppercase: false,
+            include_digits: false,
+            include_symbols: false,
+            ..PasswordSpec::default()
+        };
+        assert_eq!(
+            generate(&spec).err().map(|e| e.message).as_deref(),
+            Some("At least one character group must be included")
+        );
+    }
+
+    #[test]
+    fn passphrase_has_the_given_number_of_words() {
+        // a few words of the list have a dash themselves
+        let phrase = generate_passphrase(6, " ").unwrap();
+        let words: Vec<&str> = phrase.split(' ').collect();
+        assert_eq!(words.len(), 6, "{}", phrase);
+        assert!(words.iter().all(|word| WORDLIST
+            .lines()
+            .any(|line| line.ends_with(&format!("\t{}", word)))));
+    }
+
+    #[test]
+    fn passphrases_differ() {
+        assert_ne!(
+            generate_passphrase(6, " ").unwrap(),
+            generate_passphrase(6, " ").unwrap()
+        );
+    }
+
+    #[test]
+    fn passphrase_without_words_is_an_error() {
+        assert!(generate_passphrase(0, "-").is_err());
+    }
+
+    #[test]
+    fn pronounceable_password_alternates_consonants_and_vowels() {
+        let password = generate_pronounceable(14, false).unwrap();
+        let letters: Vec<char> = password.chars().filter(|c| *c != '-').collect();
+        assert_eq!(letters.len(), 14);
+        for (i, c) in letters.iter().enumerate() {
+            let group = if i % 2 == 0 { CONSONANTS } else { VOWELS };
+            assert!(group.contains(*c), "unexpected '{}' in {}", c, password);
+        }
+        assert!(password
+            .split('-')
+            .all(|group| group.len() <= SYLLABLE_GROUP_LENGTH));
+    }
+
+    #[test]
+    fn pronounceable_password_ends_with_the_digits() {
+        let password = generate_pronounceable(14, true).unwrap();
+        let (letters, digits) = password.split_at(password.len() - PRONOUNCEABLE_DIGIT_COUNT);
+        assert!(digits.chars().all(|c| c.is_ascii_digit()));
+        assert!(letters.chars().all(|c| c == '-' || c.is_ascii_lowercase()));
+    }
+
+    #[test]
+    fn pronounceable_passwords_differ() {
+        assert_ne!(
+            generate_pronounceable(14, false).unwrap(),
+            generate_pronounceable(14, false).unwrap()
+        );
+    }
+
+    #[test]
+    fn short_pronounceable_password_is_an_error() {
+        assert!(generate_pronounceable(4, false).is_err());
+    }
+
+    // Whether the password has only characters of the groups, and at least one of each
+    fn has_only_groups(password: &str, groups: &[&str]) -> bool {
+        password
+            .chars()
+            .all(|c| groups.iter().any(|group| group.contains(c)))
+            && groups
+                .iter()
+                .all(|group| password.chars().any(|c| group.contains(c)))
+    }
+
+    #[test]
+    fn builtin_presets_match_their_constraints() {
+        let expected: [(&str, usize, &[&str]); 5] = [
+            ("pin4", 4, &[NUMBERS]),
+            ("pin6", 6, &[NUMBERS]),
+            ("wifi", 20, &[LOW_CASE, UP_CASE, NUMBERS]),
+            ("strong", 24, &[LOW_CASE, UP_CASE, NUMBERS, SPECIAL]),
+            ("alnum16", 16, &[LOW_CASE, UP_CASE, NUMBERS]),
+        ];
+        for (name, length, groups) in expected {
+            let spec = find_preset(name, &HashMap::new()).unwrap();
+            for _ in 0..20 {
+                let password = generate(&spec).unwrap();
+                assert_eq!(password.chars().count(), length, "{}: {}", name, password);
+                assert!(has_only_groups(&password, groups), "{}: {}", name, password);
+            }
+        }
+    }
+
+    #[test]
+    fn no_ambiguous_leaves_out_the_ambiguous_characters() {
+        let spec = PasswordSpec {
+            length: 200,
+            exclude_ambiguous: true,
+            ..PasswordSpec::default()
+        };
+        for _ in 0..20 {
+            let password = generate(&spec).unwrap();
+            assert!(
+                !password.chars().any(|c| AMBIGUOUS_CHARACTERS.contains(c)),
+                "{}",
+                password
+            );
+        }
+    }
+
+    #[test]
+    fn no_ambiguous_lowers_the_entropy() {
+        let spec = PasswordSpec::default();
+        let unambiguous = PasswordSpec {
+            exclude_ambiguous: true,
+            ..PasswordSpec::default()
+        };
+        assert!(random_entropy_bits(&unambiguous) < random_entropy_bits(&spec));
+    }
+
+    #[test]
+    fn custom_preset_replaces_the_builtin_one() {
+        let custom = HashMap::from([("pin4".to_string(), preset_spec(8, false, false))]);
+        assert_eq!(find_preset("pin4", &custom).unwrap().length, 8);
+    }
+
+    #[test]
+    fn unknown_preset_lists_the_presets() {
+        let error = find_preset("nope", &HashMap::new()).unwrap_err();
+        assert!(error.message.contains("alnum16, pin4, pin6, strong, wifi"));
+    }
 }
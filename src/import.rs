@@ -0,0 +1,568 @@
+use crate::ui::input::ask_password;
+use crate::vault::entities::{Credential, Error};
+use crate::vault::keepass_vault::KeepassVault;
+use csv::{ReaderBuilder, StringRecord};
+use serde::Deserialize;
+use std::fs;
+use std::path::Path;
+use zeroize::Zeroizing;
+
+// LastPass exports secure notes as rows with this URL
+const LASTPASS_NOTE_URL: &str = "http://sn";
+// The item type of logins in Bitwarden exports
+const BITWARDEN_LOGIN_TYPE: u8 = 1;
+// The columns that a file in the passlane format needs
+const PASSLANE_COLUMNS: [&str; 3] = ["password", "service", "username"];
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ImportFormat {
+    Passlane,
+    Bitwarden,
+    OnePassword,
+    LastPass,
+    KeePass,
+}
+
+impl ImportFormat {
+    pub fn new(name: &str) -> Result<ImportFormat, Error> {
+        match name {
+            "passlane" => Ok(ImportFormat::Passlane),
+            "bitwarden" => Ok(ImportFormat::Bitwarden),
+            "1password" => Ok(ImportFormat::OnePassword),
+            "lastpass" => Ok(ImportFormat::LastPass),
+            "keepass" => Ok(ImportFormat::KeePass),
+            _ => Err(Error::new(&format!("Unknown import format '{}'", name))),
+        }
+    }
+}
+
+/// The CSV columns of the credential fields, for files whose columns are named differently
+pub struct ColumnMapping {
+    service: String,
+    username: Option<String>,
+    password: String,
+    notes: Option<String>,
+}
+
+impl ColumnMapping {
+    /// Parses a mapping like `service=Name,username=Login,password=Secret`. The service and password are required.
+    pub fn new(spec: &str) -> Result<ColumnMapping, Error> {
+        let (mut service, mut username, mut password, mut notes) = (None, None, None, None);
+        for pair in spec
+            .split(',')
+            .map(str::trim)
+            .filter(|pair| !pair.is_empty())
+        {
+            let (field, column) = pair
+                .split_once('=')
+                .map(|(field, column)| (field.trim(), column.trim().to_string()))
+                .filter(|(_, column)| !column.is_empty())
+                .ok_or_else(|| {
+                    Error::new(&format!(
+                        "Invalid column mapping '{}', use FIELD=COLUMN",
+                        pair
+                    ))
+                })?;
+            match field {
+                "service" => service = Some(column),
+                "username" => username = Some(column),
+                "password" => password = Some(column),
+                "notes" => notes = Some(column),
+                _ => {
+                    return Err(Error::new(&format!(
+                        "Unknown field '{}' in the column mapping, use service, username, password or notes",
+                        field
+                    )))
+                }
+            }
+        }
+        match (service, password) {
+            (Some(service), Some(password)) => Ok(ColumnMapping {
+                service,
+                username,
+                password,
+                notes,
+            }),
+            _ => Err(Error::new(
+                "The column mapping needs the service and password columns",
+            )),
+        }
+    }
+
+    fn columns(&self) -> Vec<&str> {
+        [
+            Some(&self.service),
+            self.username.as_ref(),
+            Some(&self.password),
+            self.notes.as_ref(),
+        ]
+        .into_iter()
+        .flatten()
+        .map(String::as_str)
+        .collect()
+    }
+}
+
+/// How to read a CSV file
+pub struct CsvOptions {
+    pub delimiter: u8,
+    /// Replaces the columns of the import format
+    pub columns: Option<ColumnMapping>,
+}
+
+impl Default for CsvOptions {
+    fn default() -> Self {
+        CsvOptions {
+            delimiter: b',',
+            columns: None,
+        }
+    }
+}
+
+/// Parses the column separator of a CSV file, a single character or `tab`
+pub fn parse_delimiter(value: &str) -> Result<u8, Error> {
+    match value {
+        "tab" | "\\t" | "\t" => Ok(b'\t'),
+        _ if value.len() == 1 && value.is_ascii() => Ok(value.as_bytes()[0]),
+        _ => Err(Error::new(&format!(
+            "Invalid delimiter '{}', use a single character or tab",
+            value
+        ))),
+    }
+}
+
+pub struct ImportedCredentials {
+    /// The column titles of a CSV file, empty for JSON and KeePass files
+    pub headers: Vec<String>,
+    pub credentials: Vec<Credential>,
+    /// The rows that could not be mapped to credentials, with the reason
+    pub skipped: Vec<String>,
+}
+
+/// Reads the credentials from an export of another password manager. The key file is used with KeePass files.
+pub fn read_credentials(
+    file_path: &str,
+    format: ImportFormat,
+    keyfile: Option<&str>,
+    csv: &CsvOptions,
+) -> Result<ImportedCredentials, Error> {
+    let delimiter = csv.delimiter;
+    if let Some(mapping) = &csv.columns {
+        return read_mapped_csv(file_path, delimiter, &mapping.columns(), &|row| {
+            map_custom_row(row, mapping)
+        });
+    }
+    match format {
+        ImportFormat::Passlane => read_passlane_csv(file_path, delimiter),
+        ImportFormat::Bitwarden if is_json(file_path)? => read_bitwarden_json(file_path),
+        ImportFormat::Bitwarden => read_mapped_csv(file_path, delimiter, &[], &map_bitwarden_row),
+        ImportFormat::OnePassword => read_mapped_csv(file_path, delimiter, &[], &map_1password_row),
+        ImportFormat::LastPass => read_mapped_csv(file_path, delimiter, &[], &map_lastpass_row),
+        ImportFormat::KeePass => read_keepass(file_path, keyfile),
+    }
+}
+
+// Fails with the list of the available columns when one of the needed ones is missing
+fn check_columns(headers: &[String], columns: &[&str]) -> Result<(), Error> {
+    match columns
+        .iter()
+        .find(|column| !headers.iter().any(|h| h.eq_ignore_ascii_case(column)))
+    {
+        Some(column) => Err(Error::new(&format!(
+            "The file has no column '{}'. Its columns are: {}. Use --columns to choose the columns to import",
+            column,
+            headers.join(", ")
+        ))),
+        None => Ok(()),
+    }
+}
+
+fn is_json(file_path: &str) -> Result<bool, Error> {
+    Ok(fs::read_to_string(file_path)?.trim_start().starts_with('{'))
+}
+
+// The columns of one CSV row, looked up by the column title ignoring case
+struct Row<'a> {
+    headers: &'a [String],
+    record: &'a StringRecord,
+}
+
+impl Row<'_> {
+    fn get(&self, column: &str) -> Option<&str> {
+        let index = self
+            .headers
+            .iter()
+            .position(|h| h.eq_ignore_ascii_case(column))?;
+        self.record
+            .get(index)
+            .map(str::trim)
+            .filter(|value| !value.is_empty())
+    }
+}
+
+fn credential(
+    service: Option<&str>,
+    username: Option<&str>,
+    password: Option<&str>,
+    notes: Option<&str>,
+) -> Result<Credential, String> {
+    let service = service.ok_or("no URL or name")?;
+    let password = password.ok_or("no password")?;
+    Ok(Credential::new(
+        None,
+        password,
+        service,
+        username.unwrap_or(""),
+        None,
+        &[],
+        notes,
+    ))
+}
+
+fn map_bitwarden_row(row: &Row) -> Result<Credential, String> {
+    if row.get("type").is_some_and(|t| t != "login") {
+        return Err("not a login".to_string());
+    }
+    credential(
+        row.get("login_uri").or(row.get("name")),
+        row.get("login_username"),
+        row.get("login_password"),
+        row.get("notes"),
+    )
+}
+
+fn map_1password_row(row: &Row) -> Result<Credential, String> {
+    credential(
+        row.get("url").or(row.get("title")),
+        row.get("username"),
+        row.get("password"),
+        row.get("notes").or(row.get("notesPlain")),
+    )
+}
+
+fn map_custom_row(row: &Row, mapping: &ColumnMapping) -> Result<Credential, String> {
+    credential(
+        row.get(&mapping.service),
+        mapping
+            .username
+            .as_deref()
+            .and_then(|column| row.get(column)),
+        row.get(&mapping.password),
+        mapping.notes.as_deref().and_then(|column| row.get(column)),
+    )
+}
+
+fn map_lastpass_row(row: &Row) -> Result<Credential, String> {
+    if row.get("url") == Some(LASTPASS_NOTE_URL) {
+        return Err("a secure note".to_string());
+    }
+    credential(
+        row.get("url").or(row.get("name")),
+        row.get("username"),
+        row.get("password"),
+        row.get("extra"),
+    )
+}
+
+// Why a row of the CSV file could not be read, without the position that the line number already tells
+fn csv_error_reason(e: &csv::Error) -> String {
+    match e.kind() {
+        csv::ErrorKind::Deserialize { err, .. } => err.to_string(),
+        csv::ErrorKind::UnequalLengths {
+            expected_len, len, ..
+        } => format!("{} fields instead of {}", len, expected_len),
+        _ => e.to_string(),
+    }
+}
+
+// Reads a file in the passlane format, the rows that can't be read are skipped with the line number
+fn read_passlane_csv(file_path: &str, delimiter: u8) -> Result<ImportedCredentials, Error> {
+    let mut reader = ReaderBuilder::new()
+        .has_headers(true)
+        .delimiter(delimiter)
+        .from_path(file_path)?;
+    let headers: Vec<String> = reader.headers()?.iter().map(String::from).collect();
+    check_columns(&headers, &PASSLANE_COLUMNS)?;
+    let mut credentials = Vec::new();
+    let mut skipped = Vec::new();
+    let mut records = reader.deserialize::<Credential>();
+    // the header is on line 1
+    let mut line = 2;
+    while let Some(result) = records.next() {
+        match result {
+            Ok(credential) if credential.password().is_empty() => {
+                skipped.push(format!("line {}: no password", line))
+            }
+            Ok(credential) => credentials.push(credential),
+            Err(e) => skipped.push(format!(
+                "line {}: {}",
+                e.position().map_or(line, |p| p.line()),
+                csv_error_reason(&e)
+            )),
+        }
+        line = records.reader().position().line();
+    }
+    Ok(ImportedCredentials {
+        headers,
+        credentials,
+        skipped,
+    })
+}
+
+// Maps the rows to credentials after checking that the file has the required columns
+fn read_mapped_csv(
+    file_path: &str,
+    delimiter: u8,
+    required_columns: &[&str],
+    map_row: &dyn Fn(&Row) -> Result<Credential, String>,
+) -> Result<ImportedCredentials, Error> {
+    let mut reader = ReaderBuilder::new()
+        .has_headers(true)
+        .flexible(true)
+        .delimiter(delimiter)
+        .from_path(file_path)?;
+    let headers: Vec<String> = reader.headers()?.iter().map(String::from).collect();
+    check_columns(&headers, required_columns)?;
+    let mut credentials = Vec::new();
+    let mut skipped = Vec::new();
+    // a quoted field can span lines, so the line is taken from the position of the record
+    for record in reader.records() {
+        let (line, mapped) = match record {
+            Ok(record) => (
+                record.position().map(|p| p.line()),
+                map_row(&Row {
+                    headers: &headers,
+                    record: &record,
+                }),
+            ),
+            Err(e) => (e.position().map(|p| p.line()), Err(e.to_string())),
+        };
+        match mapped {
+            Ok(credential) => credentials.push(credential),
+            Err(reason) => skipped.push(format!("line {}: {}", line.unwrap_or_default(), reason)),
+        }
+    }
+    Ok(ImportedCredentials {
+        headers,
+        credentials,
+        skipped,
+    })
+}
+
+#[derive(Deserialize)]
+struct BitwardenExport {
+    items: Vec<BitwardenItem>,
+}
+
+#[derive(Deserialize)]
+struct BitwardenItem {
+    #[serde(rename = "type")]
+    item_type: u8,
+    name: Option<String>,
+    notes: Option<String>,
+    login: Option<BitwardenLogin>,
+}
+
+#[derive(Deserialize)]
+struct BitwardenLogin {
+    username: Option<String>,
+    password: Option<String>,
+    uris: Option<Vec<BitwardenUri>>,
+}
+
+#[derive(Deserialize)]
+struct BitwardenUri {
+    uri: Option<String>,
+}
+
+fn map_bitwarden_item(item: &BitwardenItem) -> Result<Credential, String> {
+    let login = match &item.login {
+        Some(login) if item.item_type == BITWARDEN_LOGIN_TYPE => login,
+        _ => return Err("not a login".to_string()),
+    };
+    let uri = login
+        .uris
+        .iter()
+        .flatten()
+        .find_map(|u| u.uri.as_deref())
+        .filter(|uri| !uri.is_empty());
+    credential(
+        uri.or(item.name.as_deref().filter(|name| !name.is_empty())),
+        login.username.as_deref(),
+        login.password.as_deref().filter(|p| !p.is_empty()),
+        item.notes.as_deref(),
+    )
+}
+
+fn read_bitwarden_json(file_path: &str) -> Result<ImportedCredentials, Error> {
+    let export: BitwardenExport = serde_json::from_str(&fs::read_to_string(file_path)?)?;
+    let mut credentials = Vec::new();
+    let mut skipped = Vec::new();
+    for (index, item) in export.items.iter().enumerate() {
+        match map_bitwarden_item(item) {
+            Ok(credential) => credentials.push(credential),
+            Err(reason) => skipped.push(format!(
+                "item {} '{}': {}",
+                index + 1,
+                item.name.as_deref().unwrap_or(""),
+                reason
+            )),
+        }
+    }
+    Ok(ImportedCredentials {
+        headers: Vec::new(),
+        credentials,
+        skipped,
+    })
+}
+
+fn read_keepass(file_path: &str, keyfile: Option<&str>) -> Result<ImportedCredentials, Error> {
+    // opening a missing file would create an empty database
+    if !Path::new(file_path).exists() {
+        return Err(Error::new(&format!("File '{}' not found", file_path)));
+    }
+    let password = Zeroizing::new(ask_password(
+        "Enter the master password of the KeePass file",
+        None,
+    ));
+    let database =
+        KeepassVault::verify_master_password(&password, file_path, keyfile.map(String::from))?
+            .ok_or_else(|| {
+                Error::new(&format!(
+                    "Wrong master password or key file for '{}'",
+                    file_path
+                ))
+            })?;
+    let mut credentials = Vec::new();
+    let mut skipped = Vec::new();
+    for result in database.import_credentials() {
+        match result {
+            Ok(credential) => credentials.push(credential),
+            Err(reason) => skipped.push(reason),
+        }
+    }
+    Ok(ImportedCredentials {
+        headers: Vec::new(),
+        credentials,
+        skipped,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::test_file;
+
+    fn services(imported: &ImportedCredentials) -> Vec<&str> {
+        imported.credentials.iter().map(|c| c.service()).collect()
+    }
+
+    #[test]
+    fn reads_a_semicolon_delimited_file() {
+        let path = test_file(
+            "semicolon.csv",
+            "service;username;password\nexample.com;john;s3cret,with,commas\nother.org;jane;pw2\n",
+        );
+        let options = CsvOptions {
+            delimiter: parse_delimiter(";").unwrap(),
+            columns: None,
+        };
+        let imported = read_credentials(&path, ImportFormat::Passlane, None, &options).unwrap();
+        assert_eq!(services(&imported), ["example.com", "other.org"]);
+        assert_eq!(imported.credentials[0].password(), "s3cret,with,commas");
+        assert!(imported.skipped.is_empty());
+    }
+
+    #[test]
+    fn reads_the_columns_of_a_custom_mapping() {
+        let path = test_file(
+            "mapping.csv",
+            "Name,Login,Secret,Comment\nexample.com,john,pw1,first\nother.org,,pw2,\n",
+        );
+        let options = CsvOptions {
+            delimiter: b',',
+            columns: Some(
+                ColumnMapping::new("service=Name, username=Login, password=Secret, notes=Comment")
+                    .unwrap(),
+            ),
+        };
+        let imported = read_credentials(&path, ImportFormat::Passlane, None, &options).unwrap();
+        assert_eq!(services(&imported), ["example.com", "other.org"]);
+        let first = &imported.credentials[0];
+        assert_eq!(
+            (first.username(), first.password(), first.notes()),
+            ("john", "pw1", Some("first"))
+        );
+        assert_eq!(imported.credentials[1].username(), "");
+    }
+
+    #[test]
+    fn malformed_rows_are_skipped_with_their_line_numbers() {
+        let path = test_file(
+            "malformed.csv",
+            "password,service,username\npw1,example.com,john\n,empty.com,jane\npw3,short.com\npw4,\"multi\nline.com\",joe\npw5,last.com,jim\n",
+        );
+        let imported =
+            read_credentials(&path, ImportFormat::Passlane, None, &CsvOptions::default()).unwrap();
+        assert_eq!(
+            services(&imported),
+            ["example.com", "multi\nline.com", "last.com"]
+        );
+        assert_eq!(
+            imported.skipped,
+            ["line 3: no password", "line 4: 2 fields instead of 3"]
+        );
+    }
+
+    #[test]
+    fn rows_without_a_service_are_skipped_in_other_formats() {
+        let path = test_file(
+            "bitwarden.csv",
+            "type,name,login_uri,login_username,login_password\nlogin,Example,https://example.com,john,pw1\nnote,Secret note,,,\nlogin,,,jane,pw2\n",
+        );
+        let imported =
+            read_credentials(&path, ImportFormat::Bitwarden, None, &CsvOptions::default()).unwrap();
+        assert_eq!(services(&imported), ["https://example.com"]);
+        assert_eq!(
+            imported.skipped,
+            ["line 3: not a login", "line 4: no URL or name"]
+        );
+    }
+
+    #[test]
+    fn lines_are_counted_past_multiline_notes_in_other_formats() {
+        let path = test_file(
+            "lastpass.csv",
+            "url,username,password,extra,name\nhttps://example.com,john,pw1,\"first\nsecond\nthird\",Example\n,jane,,,\nhttps://other.org,jim,pw3,,Other\n",
+        );
+        let imported =
+            read_credentials(&path, ImportFormat::LastPass, None, &CsvOptions::default()).unwrap();
+        assert_eq!(
+            services(&imported),
+            ["https://example.com", "https://other.org"]
+        );
+        assert_eq!(
+            imported.credentials[0].notes(),
+            Some("first\nsecond\nthird")
+        );
+        assert_eq!(imported.skipped, ["line 5: no URL or name"]);
+    }
+
+    #[test]
+    fn mapping_needs_the_service_and_password() {
+        assert!(ColumnMapping::new("service=Name").is_err());
+        assert!(ColumnMapping::new("service=Name,password=").is_err());
+        assert!(ColumnMapping::new("service=Name,password=Secret,url=Link").is_err());
+    }
+
+    #[test]
+    fn missing_mapped_column_is_an_error() {
+        let path = test_file("missing-column.csv", "Name,Secret\nexample.com,pw1\n");
+        let options = CsvOptions {
+            delimiter: b',',
+            columns: Some(ColumnMapping::new("service=Name,password=Password").unwrap()),
+        };
+        let result = read_credentials(&path, ImportFormat::Passlane, None, &options);
+        assert!(result.is_err_and(|e| e.message.contains("no column 'Password'")));
+    }
+}
@@ -1,3 +1,4 @@
+use crate::store;
 use crate::vault::entities::Error;
 use keyring::Entry;
 use log::debug;
@@ -14,32 +15,40 @@ impl From<keyring::Error> for Error {
     }
 }
 
+// Each profile keeps its master passwords in its own keychain entries
+fn account() -> String {
+    match store::get_profile() {
+        Some(profile) => format!("{}-{}", USERNAME, profile),
+        None => USERNAME.to_string(),
+    }
+}
+
 pub fn save_master_password(pwd: &str) -> Result<(), Error> {
-    let entry = Entry::new(SERVICE_NAME, USERNAME)?;
+    let entry = Entry::new(SERVICE_NAME, &account())?;
     Ok(entry.set_password(pwd)?)
 }
 pub fn save_totp_master_password(pwd: &str) -> Result<(), Error> {
-    let entry = Entry::new(SERVICE_NAME_TOTP, USERNAME)?;
+    let entry = Entry::new(SERVICE_NAME_TOTP, &account())?;
     Ok(entry.set_password(pwd)?)
 }
 
 pub fn get_master_password() -> Result<String, Error> {
     debug!("Getting master password from keychain");
-    let entry = Entry::new(SERVICE_NAME, USERNAME)?;
+    let entry = Entry::new(SERVICE_NAME, &account())?;
     Ok(entry.get_password()?)
 }
 
 pub fn delete_master_password() -> Result<(), Error> {
-    let entry = Entry::new(SERVICE_NAME, USERNAME)?;
+    let entry = Entry::new(SERVICE_NAME, &account())?;
     Ok(entry.delete_password()?)
 }
 
 pub(crate) fn get_totp_master_password() -> Result<String, Error> {
-    let entry = Entry::new(SERVICE_NAME_TOTP, USERNAME)?;
+    let entry = Entry::new(SERVICE_NAME_TOTP, &account())?;
     Ok(entry.get_password()?)
 }
 
 pub(crate) fn delete_totp_master_password() -> Result<(), Error> {
-    let entry = Entry::new(SERVICE_NAME_TOTP, USERNAME)?;
+    let entry = Entry::new(SERVICE_NAME_TOTP, &account())?;
     Ok(entry.delete_password()?)
 }
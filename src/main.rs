@@ -1,26 +1,49 @@
-extern crate clipboard;
 extern crate magic_crypt;
 
 mod actions;
+mod clipboard;
+mod config;
 mod crypto;
+mod import;
 mod keychain;
+mod remote;
 mod store;
+#[cfg(test)]
+mod test_util;
 mod ui;
 mod vault;
 
 use crate::actions::add::AddAction;
+use crate::actions::backup_key::BackupKeyAction;
+use crate::actions::check::CheckAction;
+use crate::actions::clear_clipboard::ClearClipboardAction;
+use crate::actions::completions::CompletionsAction;
 use crate::actions::delete::DeleteAction;
+use crate::actions::diff::DiffAction;
 use crate::actions::edit::EditAction;
 use crate::actions::export::ExportAction;
 use crate::actions::generate::GeneratePasswordAction;
 use crate::actions::help::PrintHelpAction;
+use crate::actions::history::HistoryAction;
 use crate::actions::import::ImportCsvAction;
 use crate::actions::lock::LockAction;
+use crate::actions::move_service::MoveAction;
+use crate::actions::open::OpenAction;
+use crate::actions::profiles::ProfilesAction;
+use crate::actions::recovery::RecoveryAction;
+use crate::actions::restore_key::RestoreKeyAction;
+use crate::actions::rotate_all::RotateAllAction;
 use crate::actions::show::ShowAction;
+use crate::actions::stats::StatsAction;
+use crate::actions::status::StatusAction;
+use crate::actions::sync::SyncAction;
 use crate::actions::unlock::UnlockAction;
+use crate::crypto::PasswordGenerator;
 use actions::*;
-use clap::{arg, ArgAction, Command};
+use clap::{arg, value_parser, ArgAction, ArgGroup, ArgMatches, Command};
+use clap_complete::Shell;
 use init::InitAction;
+use log::LevelFilter;
 use std::env;
 
 fn cli() -> Command {
@@ -29,10 +52,56 @@ fn cli() -> Command {
         .subcommand_required(false)
         .arg_required_else_help(false)
         .allow_external_subcommands(true)
+        .arg(arg!(--"clear-after" <SECONDS> "Clear a copied value from the clipboard after this many seconds, 30 by default. Use 0 to keep it.").required(false).global(true).value_parser(value_parser!(u64)))
+        .arg(arg!(--profile <NAME> "Use the vault and settings of this profile in the profiles/ directory instead of the default ones.").required(false).global(true))
+        .arg(arg!(--"log-level" <LEVEL> "Print the log messages of this level and the more severe ones to stderr. RUST_LOG is used by default.").required(false).global(true).value_parser(["error", "warn", "info", "debug", "trace"]))
+        .arg(arg!(
+            -L --"verbose-log" ... "Print more log messages to stderr: info with -L, debug with -LL and trace with -LLL."
+        ).action(ArgAction::Count).global(true).conflicts_with("log-level"))
+        .arg(arg!(--"master-password-file" <PATH> "Read the master password of the vault from the first line of this file. Keep the file readable only by you.").required(false).global(true))
+        .arg(arg!(
+            --"fail-on-empty" "Exit with code 2 when a search finds no matches, instead of succeeding."
+        ).action(ArgAction::SetTrue).global(true))
+        .arg(arg!(
+            -j --json "Print the output of show, status, stats and diff as JSON for scripts, without prompts or clipboard use."
+        ).action(ArgAction::SetTrue).global(true))
+        .arg(arg!(
+            --pretty "Indent the JSON output. This is the default when printing to a terminal."
+        ).action(ArgAction::SetTrue).global(true).requires("json").conflicts_with("compact"))
+        .arg(arg!(
+            --compact "Print the JSON output on one line. This is the default when the output is piped."
+        ).action(ArgAction::SetTrue).global(true).requires("json"))
         .subcommand(
             Command::new("init")
                 .about("Initialize passlane. Walks you through the configuration process.")
         )
+        .subcommand(
+            Command::new("generate")
+                .about("Generates a new password and copies it to the clipboard. This is also done when running without a command.")
+                .arg(arg!(-o --out <PATH> "Write the password to the given file, readable only by you, instead of the clipboard.").required(false))
+                .arg(arg!(
+                    -f --force "Overwrite the file given with --out if it exists."
+                ).action(ArgAction::SetTrue))
+                .arg(arg!(
+                    --"no-copy" "Print the password without copying it to the clipboard."
+                ).action(ArgAction::SetTrue).conflicts_with("out"))
+                .arg(arg!(--length <LENGTH> "The length of the password, 15 by default.").required(false).value_parser(value_parser!(usize)))
+                .arg(arg!(
+                    --"no-symbols" "Generate the password without special characters."
+                ).action(ArgAction::SetTrue))
+                .arg(arg!(
+                    --"no-ambiguous" "Leave out characters that are easy to confuse, like l, 1, I, O and 0."
+                ).action(ArgAction::SetTrue).conflicts_with_all(["words", "pronounceable", "preset"]))
+                .arg(arg!(--words <COUNT> "Generate a passphrase of this many words instead of a random password.").required(false).value_parser(value_parser!(usize)).conflicts_with_all(["length", "no-symbols"]))
+                .arg(arg!(--separator <SEPARATOR> "The separator of the passphrase words, '-' by default.").required(false).requires("words"))
+                .arg(arg!(
+                    --pronounceable "Generate a password of syllables that is easy to read aloud. The length is the number of letters."
+                ).action(ArgAction::SetTrue).conflicts_with_all(["words", "no-symbols"]))
+                .arg(arg!(
+                    --digits "Add two digits to the end of the pronounceable password, for sites that require numbers."
+                ).action(ArgAction::SetTrue).requires("pronounceable"))
+                .arg(arg!(--preset <NAME> "Generate the password with a preset for common site policies: pin4, pin6, wifi, strong, alnum16 or one of the presets in config.toml. --length overrides the length of the preset.").required(false).conflicts_with_all(["words", "pronounceable", "no-symbols"]))
+        )
         .subcommand(
             Command::new("add")
                 .about("Adds an item to the vault. Without arguments adds a new credential, use -p to add a payment card and -n to add a secure note.")
@@ -48,9 +117,39 @@ fn cli() -> Command {
                 .arg(arg!(
                     -g --generate "Generate the password to be saved."
                 ).action(ArgAction::SetTrue))
+                .arg(arg!(--length <LENGTH> "The length of the generated password, 15 by default.").required(false).value_parser(value_parser!(usize)).requires("generate"))
+                .arg(arg!(
+                    --"no-symbols" "Generate the password without special characters."
+                ).action(ArgAction::SetTrue).requires("generate"))
+                .arg(arg!(
+                    --"no-ambiguous" "Leave out characters that are easy to confuse, like l, 1, I, O and 0."
+                ).action(ArgAction::SetTrue).requires("generate").conflicts_with_all(["words", "pronounceable", "preset"]))
+                .arg(arg!(--words <COUNT> "Generate a passphrase of this many words instead of a random password.").required(false).value_parser(value_parser!(usize)).requires("generate").conflicts_with_all(["length", "no-symbols"]))
+                .arg(arg!(--separator <SEPARATOR> "The separator of the passphrase words, '-' by default.").required(false).requires("words"))
+                .arg(arg!(
+                    --pronounceable "Generate a password of syllables that is easy to read aloud. The length is the number of letters."
+                ).action(ArgAction::SetTrue).requires("generate").conflicts_with_all(["words", "no-symbols"]))
+                .arg(arg!(
+                    --digits "Add two digits to the end of the pronounceable password, for sites that require numbers."
+                ).action(ArgAction::SetTrue).requires("pronounceable"))
+                .arg(arg!(--preset <NAME> "Generate the password with a preset for common site policies: pin4, pin6, wifi, strong, alnum16 or one of the presets in config.toml. --length overrides the length of the preset.").required(false).requires("generate").conflicts_with_all(["words", "pronounceable", "no-symbols"]))
                 .arg(arg!(
                     -l --clipboard "Get the password to save from the clipboard."
                 ).action(ArgAction::SetTrue))
+                .arg(arg!(
+                    --"no-username" "Save the credential without a username, for example an API key or a wifi password."
+                ).action(ArgAction::SetTrue))
+                .arg(arg!(
+                    --"no-auto-tags" "Do not apply the tags from the rules in ~/.passlane/.tag_rules."
+                ).action(ArgAction::SetTrue))
+                .arg(arg!(
+                    -f --force "Save the service as entered even if it is not a valid URL."
+                ).action(ArgAction::SetTrue))
+                .arg(arg!(--service <SERVICE> "The URL or name of the service, instead of asking it.").required(false))
+                .arg(arg!(--username <USERNAME> "The username, instead of asking it.").required(false).conflicts_with("no-username"))
+                .arg(arg!(--password <PASSWORD> "The password to save, instead of asking it. Note that other users may see it in the process list.").required(false).conflicts_with_all(["generate", "clipboard"]))
+                .arg(arg!(--"with-notes" <NOTES> "Notes to save with the credential, like answers to security questions. The -n option adds a secure note instead.").required(false))
+                .arg(arg!(-t --tag <TAG> "A tag to save with the credential, like work or finance. Can be given several times.").required(false).action(ArgAction::Append))
         )
         .subcommand(
             Command::new("edit")
@@ -59,13 +158,41 @@ fn cli() -> Command {
                 .arg(arg!(-p --payments "Edit payment cards.").action(ArgAction::SetTrue))
                 .arg(arg!(-n --notes "Edit secure notes.").action(ArgAction::SetTrue))
                 .arg(arg!(-o --otp "Edit One Time Password authorizer.").action(ArgAction::SetTrue))
-                .arg(arg!(<REGEXP> "The regular expression used to search services whose credentials to edit.").group("search").required(false))
+                .arg(arg!(<REGEXP> "The text to search in the services and usernames of the credentials to edit, ignoring case.").group("search").required(false))
+                .arg(arg!(
+                    --regex "Match REGEXP as a regular expression instead of plain text."
+                ).action(ArgAction::SetTrue).requires("search").conflicts_with_all(["payments", "notes", "otp"]))
+                .arg(arg!(-t --tag <TAG> "Replace the tags of the credential with this one. Can be given several times.").required(false).action(ArgAction::Append).conflicts_with_all(["payments", "notes", "otp"]))
                 .arg_required_else_help(true)
         )
         .subcommand(
             Command::new("csv")
                 .about("Imports credentials from a CSV file.")
                 .arg(arg!(<FILE_PATH> "The the CSV file to import."))
+                .arg(arg!(
+                    -f --force "Import even if the file looks like it has mislabeled columns."
+                ).action(ArgAction::SetTrue))
+                .arg(arg!(
+                    -r --recovery "Import recovery codes from a file with service, username and codes columns."
+                ).action(ArgAction::SetTrue))
+                .arg(arg!(
+                    --from <FORMAT> "The password manager that exported the file. Bitwarden exports can be CSV or JSON, keepass reads a KDBX file."
+                ).required(false).value_parser(["passlane", "bitwarden", "1password", "lastpass", "keepass"]).default_value("passlane").conflicts_with("recovery"))
+                .arg(arg!(
+                    --"key-file" <PATH> "The key file of the KeePass file, used with --from keepass."
+                ).required(false))
+                .arg(arg!(
+                    --delimiter <CHAR> "The character that separates the columns, ',' by default. Use tab for tab separated files."
+                ).required(false).conflicts_with("recovery"))
+                .arg(arg!(
+                    --columns <MAPPING> "The columns of the credential fields, like service=Name,username=Login,password=Secret. The notes field can be mapped too."
+                ).required(false).conflicts_with_all(["from", "recovery"]))
+                .arg(arg!(
+                    --"on-conflict" <MODE> "What to do with a credential whose service and username are already saved: skip it, update the password if the imported one is newer, or save a duplicate."
+                ).required(false).value_parser(["skip", "update", "duplicate"]).default_value("skip").conflicts_with("recovery"))
+                .arg(arg!(
+                    --strict "Import nothing if any row of the file can't be imported, instead of skipping the row."
+                ).action(ArgAction::SetTrue).conflicts_with("recovery"))
         )
         .subcommand(
             Command::new("delete")
@@ -82,7 +209,19 @@ fn cli() -> Command {
                 .arg(arg!(
                     -o --otp "Delete One Time Password authorizer."
                 ).action(ArgAction::SetTrue))
-                .arg(arg!(<REGEXP> "The regular expression used to search services whose credentials to delete.").group("search").required(false))
+                .arg(arg!(<REGEXP> "The text to search in the services and usernames of the credentials to delete, ignoring case.").group("search").required(false))
+                .arg(arg!(
+                    --regex "Match REGEXP as a regular expression instead of plain text."
+                ).action(ArgAction::SetTrue).requires("search").conflicts_with_all(["payments", "notes", "otp"]))
+                .arg(arg!(
+                    --"dry-run" "Show the entries that would be deleted without deleting anything."
+                ).action(ArgAction::SetTrue))
+                .arg(arg!(
+                    --all "Delete all the credentials that match without asking for a row."
+                ).action(ArgAction::SetTrue).requires("search").conflicts_with_all(["payments", "notes", "otp"]))
+                .arg(arg!(
+                    -y --yes "Don't ask for a confirmation before deleting, used with --all."
+                ).action(ArgAction::SetTrue).requires("all"))
                 .arg_required_else_help(true)
         )
         .subcommand(
@@ -93,17 +232,42 @@ fn cli() -> Command {
                 ).action(ArgAction::SetTrue))
                 .arg(arg!(
                     -p --payments "Shows payment cards."
-                ).action(ArgAction::SetTrue))
+                ).action(ArgAction::SetTrue).conflicts_with("json"))
                 .arg(arg!(
                     -o --otp "Shows one time passwords (OTPs)"
-                ).action(ArgAction::SetTrue))
+                ).action(ArgAction::SetTrue).conflicts_with("json"))
                 .arg(arg!(
                     -n --notes "Shows secure notes."
-                ).action(ArgAction::SetTrue))
+                ).action(ArgAction::SetTrue).conflicts_with("json"))
                 .arg(arg!(
-                    -c --credentials "Shows credentials by searching with the specified regular expression."
+                    -c --credentials "Shows credentials by searching with REGEXP."
                 ).action(ArgAction::SetTrue).requires("search"))
-                .arg(arg!(<REGEXP> "Regular expression used to search services to show.").group("search").required(false))
+                .arg(arg!(<REGEXP> "The text to search in the services and usernames of the credentials to show, ignoring case.").group("search").required(false))
+                .arg(arg!(
+                    --regex "Match REGEXP as a regular expression instead of plain text."
+                ).action(ArgAction::SetTrue).requires("search").conflicts_with_all(["payments", "notes", "otp"]))
+                .arg(arg!(-t --tag <TAG> "Only show the credentials that have this tag. When given several times the credentials need to have all of them.").required(false).action(ArgAction::Append).conflicts_with_all(["payments", "notes", "otp"]))
+                .arg(arg!(--field <FIELD> "Only search the REGEXP in this field of the credentials instead of the service and the username. The service is the URL of the credential.").required(false).value_parser(["service", "url", "username", "notes"]).requires("search").conflicts_with_all(["payments", "notes", "otp"]))
+                .arg(arg!(--limit <COUNT> "Show at most this many credentials. The total number of matches is told.").required(false).value_parser(value_parser!(u64).range(1..)).conflicts_with_all(["payments", "notes", "otp"]))
+                .arg(arg!(--page <PAGE> "The page of credentials to show with --limit, 1 by default.").required(false).value_parser(value_parser!(u64).range(1..)).requires("limit"))
+                .arg(arg!(--sort <ORDER> "Sort the credentials by service, username, creation time or modification time, the oldest first.").required(false).value_parser(["service", "username", "created", "modified"]).conflicts_with_all(["payments", "notes", "otp"]))
+                .arg(arg!(--reverse "Reverse the order of --sort.").action(ArgAction::SetTrue).requires("sort"))
+                .arg(arg!(-u --user <TEXT> "Only show the credentials whose username contains this text, ignoring case.").required(false).conflicts_with_all(["payments", "notes", "otp", "exact-service"]))
+                .arg(arg!(
+                    --"exact-service" <SERVICE> "Show the one credential whose service is exactly this. Exits with code 2 if none and 3 if several credentials match."
+                ).required(false).conflicts_with("search"))
+                .arg(arg!(
+                    --username <USERNAME> "Only match the credential with exactly this username, used with --exact-service."
+                ).required(false).requires("exact-service"))
+                .arg(arg!(
+                    --copy <ROW> "Copy the password on this row of the matches table without asking, for scripts."
+                ).required(false).value_parser(value_parser!(usize)).conflicts_with("json"))
+                .arg(arg!(
+                    -q --quiet "Don't print the matches table, used with --copy."
+                ).action(ArgAction::SetTrue).requires("copy"))
+                .arg(arg!(
+                    -s --"show-password" "Print the password of the chosen credential instead of copying it to the clipboard. With --json the passwords are included in the JSON."
+                ).action(ArgAction::SetTrue))
                 .arg_required_else_help(true)
         )
         .subcommand(
@@ -116,6 +280,9 @@ fn cli() -> Command {
                 .arg(arg!(
                     -o --otp "Opens the one time passwords vault"
                 ).action(ArgAction::SetTrue))
+                .arg(arg!(
+                    --"for" <MINUTES> "Lock the vault again after this many minutes."
+                ).required(false).value_parser(value_parser!(u64)))
         )
         .subcommand(
             Command::new("export")
@@ -129,13 +296,192 @@ fn cli() -> Command {
                 .arg(arg!(
                     -o --otp "Shows one time passwords (OTPs)"
                 ).action(ArgAction::SetTrue))
-                .arg(arg!(<file_path> "The the CSV file to export to."))
+                .arg(arg!(
+                    -r --recovery "Export recovery codes."
+                ).action(ArgAction::SetTrue))
+                .arg(arg!(
+                    --"only-weak" "Export only the credentials with weak passwords."
+                ).action(ArgAction::SetTrue))
+                .arg(arg!(
+                    --"only-reused" "Export only the credentials whose password is used by other credentials too."
+                ).action(ArgAction::SetTrue))
+                .arg(arg!(--format <FORMAT> "The format of the exported credentials.").required(false).value_parser(["csv", "json"]).default_value("csv"))
+                .arg(arg!(
+                    -f --force "Overwrite the file if it exists."
+                ).action(ArgAction::SetTrue))
+                .arg(arg!(<file_path> "The the file to export to."))
+        )
+        .subcommand(
+            Command::new("recovery")
+                .about("Shows the remaining recovery codes of a service and marks a used one by removing it.")
+                .arg(arg!(
+                    -a --add "Add recovery codes for a service."
+                ).action(ArgAction::SetTrue))
+                .arg(arg!(<REGEXP> "Regular expression used to search services.").required(false))
+        )
+        .subcommand(
+            Command::new("diff")
+                .about("Compares the credentials in the vault against a CSV file, for example a backup. Passwords are not shown, only whether they differ.")
+                .arg(arg!(<FILE_PATH> "The CSV file to compare against."))
+        )
+        .subcommand(
+            Command::new("move")
+                .about("Changes the service of a credential, for example when the service is renamed. The password and the other fields are kept.")
+                .arg(arg!(<REGEXP> "The text to search in the services and usernames to find the credential to move, ignoring case."))
+                .arg(arg!(
+                    --regex "Match REGEXP as a regular expression instead of plain text."
+                ).action(ArgAction::SetTrue))
+                .arg(arg!(<SERVICE> "The new URL or name of the service."))
+                .arg(arg!(
+                    -f --force "Save the service even if it looks like an invalid URL."
+                ).action(ArgAction::SetTrue))
+        )
+        .subcommand(
+            Command::new("history")
+                .about("Shows the earlier passwords of a credential and copies the chosen one to the clipboard.")
+                .arg(arg!(
+                    -v --verbose "Show the earlier passwords in clear text."
+                ).action(ArgAction::SetTrue))
+                .arg(arg!(<REGEXP> "The text to search in the services and usernames, ignoring case."))
+                .arg(arg!(
+                    --regex "Match REGEXP as a regular expression instead of plain text."
+                ).action(ArgAction::SetTrue))
+        )
+        .subcommand(
+            Command::new("open")
+                .about("Copies the password of a credential to the clipboard and opens the service in the browser.")
+                .arg(arg!(<REGEXP> "The text to search in the services and usernames, ignoring case."))
+                .arg(arg!(
+                    --regex "Match REGEXP as a regular expression instead of plain text."
+                ).action(ArgAction::SetTrue))
+        )
+        .subcommand(
+            Command::new("rotate-all")
+                .about("Generates new passwords for all credentials that have the given tag, are older than the given age or weaker than the given strength. The old passwords are kept in the entry history.")
+                .arg(arg!(-t --tag <TAG> "The tag of the credentials to rotate.").required(false))
+                .arg(arg!(--"older-than" <DAYS> "Rotate the passwords that were last changed more than this many days ago.").required(false).value_parser(value_parser!(i64).range(0..)))
+                .arg(arg!(--"weaker-than" <STRENGTH> "Rotate the passwords that are weaker than this: fair or strong.").required(false).value_parser(["fair", "strong"]))
+                .arg(arg!(
+                    --"dry-run" "Show the credentials that would be rotated without changing anything."
+                ).action(ArgAction::SetTrue))
+                .arg(arg!(
+                    -y --yes "Don't ask for a confirmation before rotating. Needed when stdin is not a terminal."
+                ).action(ArgAction::SetTrue).conflicts_with("dry-run"))
+                .group(ArgGroup::new("criteria").args(["tag", "older-than", "weaker-than"]).required(true).multiple(true))
+        )
+        .subcommand(
+            Command::new("clear-clipboard")
+                .about("Clears the clipboard immediately.")
+                .arg(arg!(--after <SECONDS> "Wait this many seconds and restore the clipboard as it was before the value read from stdin was copied. Used by passlane itself to clear copied passwords.").required(false).hide(true).value_parser(value_parser!(u64)))
+        )
+        .subcommand(
+            Command::new("completions")
+                .about("Prints the shell completion script for bash, zsh, fish, elvish or powershell.")
+                .arg(arg!(<SHELL> "The shell to print the completions for.").value_parser(value_parser!(Shell)))
+        )
+        .subcommand(
+            Command::new("check")
+                .about("Checks the passwords against the breached passwords of Have I Been Pwned. Only the first 5 characters of the SHA-1 hash of each password are sent.")
+                .arg(arg!(
+                    --offline "Don't use the network, check the passwords only against the built-in list of common passwords."
+                ).action(ArgAction::SetTrue))
+        )
+        .subcommand(
+            Command::new("stats")
+                .visible_alias("count")
+                .about("Shows the number of entries in the vault and how strong the passwords are.")
+        )
+        .subcommand(
+            Command::new("status")
+                .about("Shows the profile, the vault files and whether the vaults are locked.")
+        )
+        .subcommand(
+            Command::new("sync")
+                .about("Syncs the vault file with the WebDAV or S3 server in the [sync] settings. The copy that has changed since the last sync replaces the other one.")
+                .arg(arg!(
+                    --upload "Replace the remote vault with the local one."
+                ).action(ArgAction::SetTrue).conflicts_with("download"))
+                .arg(arg!(
+                    --download "Replace the local vault with the remote one."
+                ).action(ArgAction::SetTrue))
+        )
+        .subcommand(
+            Command::new("profiles")
+                .about("Lists the profiles. The current one is marked with '*'.")
+        )
+        .subcommand(
+            Command::new("backup-key")
+                .about("Backs up the key file of the vault to a Keepass file that is protected with a passphrase of its own.")
+                .arg(arg!(<FILE_PATH> "The file to write the backup to."))
+                .arg(arg!(
+                    -o --otp "Back up the key file of the One Time Passwords vault."
+                ).action(ArgAction::SetTrue))
+                .arg(arg!(
+                    -f --force "Overwrite the file if it already exists."
+                ).action(ArgAction::SetTrue))
+        )
+        .subcommand(
+            Command::new("restore-key")
+                .about("Restores the key file of the vault from a backup made with backup-key.")
+                .arg(arg!(<FILE_PATH> "The backup file."))
+                .arg(arg!(--out <PATH> "Where to write the key file, by default the key file path of the vault.").required(false))
+                .arg(arg!(
+                    -o --otp "Restore the key file of the One Time Passwords vault."
+                ).action(ArgAction::SetTrue))
+                .arg(arg!(
+                    -f --force "Overwrite the key file if it already exists."
+                ).action(ArgAction::SetTrue))
         )
 }
 
+// The commands that print JSON with the global --json flag
+const JSON_COMMANDS: [&str; 4] = ["show", "status", "stats", "diff"];
+
+// The level of --log-level, or the one that the count of -L picks, None to use RUST_LOG
+fn log_level(matches: &ArgMatches) -> Option<LevelFilter> {
+    if let Some(level) = matches.get_one::<String>("log-level") {
+        return Some(level.parse().expect("a valid log level"));
+    }
+    match matches.get_count("verbose-log") {
+        0 => None,
+        1 => Some(LevelFilter::Info),
+        2 => Some(LevelFilter::Debug),
+        _ => Some(LevelFilter::Trace),
+    }
+}
+
 fn main() {
-    env_logger::init();
     let matches = cli().get_matches();
+    // the logs go to stderr, RUST_LOG still works and the options override its default level
+    let mut logger = env_logger::Builder::from_default_env();
+    if let Some(level) = log_level(&matches) {
+        logger.filter_level(level);
+    }
+    logger.init();
+    if let Some(profile) = matches.get_one::<String>("profile") {
+        store::set_profile(profile).unwrap_or_else(|e| {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        });
+    }
+    set_fail_on_empty(matches.get_flag("fail-on-empty"));
+    set_clipboard_timeout(clipboard_timeout(&matches));
+    if let Some(path) = matches.get_one::<String>("master-password-file") {
+        set_master_password_file(path);
+    }
+    if matches.get_flag("json") {
+        if let Some((name, _)) = matches
+            .subcommand()
+            .filter(|(name, _)| !JSON_COMMANDS.contains(name))
+        {
+            eprintln!(
+                "The {} command has no JSON output, --json works with: {}",
+                name,
+                JSON_COMMANDS.join(", ")
+            );
+            std::process::exit(2);
+        }
+    }
 
     enum VaultAction {
         Action(Box<dyn Action>),
@@ -164,9 +510,58 @@ fn main() {
         Some(("edit", sub_matches)) => {
             VaultAction::UnlockingAction(Box::new(EditAction::new(sub_matches)))
         }
+        Some(("diff", sub_matches)) => {
+            VaultAction::UnlockingAction(Box::new(DiffAction::new(sub_matches)))
+        }
+        Some(("move", sub_matches)) => {
+            VaultAction::UnlockingAction(Box::new(MoveAction::new(sub_matches)))
+        }
+        Some(("history", sub_matches)) => {
+            VaultAction::UnlockingAction(Box::new(HistoryAction::new(sub_matches)))
+        }
+        Some(("open", sub_matches)) => {
+            VaultAction::UnlockingAction(Box::new(OpenAction::new(sub_matches)))
+        }
+        Some(("rotate-all", sub_matches)) => {
+            VaultAction::UnlockingAction(Box::new(RotateAllAction::new(sub_matches)))
+        }
+        Some(("recovery", sub_matches)) => {
+            VaultAction::UnlockingAction(Box::new(RecoveryAction::new(sub_matches)))
+        }
+        Some(("generate", sub_matches)) => {
+            VaultAction::Action(Box::new(GeneratePasswordAction::new(sub_matches)))
+        }
+        Some(("clear-clipboard", sub_matches)) => {
+            VaultAction::Action(Box::new(ClearClipboardAction::new(sub_matches)))
+        }
+        Some(("profiles", _)) => VaultAction::Action(Box::new(ProfilesAction)),
+        Some(("check", sub_matches)) => {
+            VaultAction::UnlockingAction(Box::new(CheckAction::new(sub_matches)))
+        }
+        Some(("stats", sub_matches)) => {
+            VaultAction::UnlockingAction(Box::new(StatsAction::new(sub_matches)))
+        }
+        Some(("backup-key", sub_matches)) => {
+            VaultAction::Action(Box::new(BackupKeyAction::new(sub_matches)))
+        }
+        Some(("restore-key", sub_matches)) => {
+            VaultAction::Action(Box::new(RestoreKeyAction::new(sub_matches)))
+        }
+        Some(("status", sub_matches)) => {
+            VaultAction::Action(Box::new(StatusAction::new(sub_matches)))
+        }
+        Some(("sync", sub_matches)) => VaultAction::Action(Box::new(SyncAction::new(sub_matches))),
+        Some(("completions", sub_matches)) => {
+            VaultAction::Action(Box::new(CompletionsAction::new(cli(), sub_matches)))
+        }
         _ => {
             if env::args().len() == 1 {
-                VaultAction::Action(Box::new(GeneratePasswordAction {}))
+                VaultAction::Action(Box::new(GeneratePasswordAction {
+                    out: None,
+                    force: false,
+                    no_copy: false,
+                    password_generator: PasswordGenerator::Random(default_password_spec()),
+                }))
             } else {
                 VaultAction::Action(Box::new(PrintHelpAction::new(cli())))
             }
@@ -177,19 +572,52 @@ fn main() {
             action
                 .run()
                 .map(|msg| println!("{}", msg))
-                .unwrap_or_else(|e| {
-                    eprintln!("{}", e);
-                    std::process::exit(1);
-                });
+                .unwrap_or_else(|e| exit_with(1, &e.to_string()));
         }
         VaultAction::UnlockingAction(action) => {
             action
                 .execute()
                 .map(|msg| println!("{}", msg.unwrap_or("".to_string())))
-                .unwrap_or_else(|e| {
-                    eprintln!("{}", e);
-                    std::process::exit(1);
-                });
+                .unwrap_or_else(|e| exit_with(1, &e.to_string()));
         }
     }
+    clear_clipboard_later();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn level(args: &[&str]) -> Option<LevelFilter> {
+        log_level(&cli().get_matches_from([&["passlane"], args].concat()))
+    }
+
+    #[test]
+    fn repeated_verbose_log_raises_the_level() {
+        assert_eq!(level(&["show", "github"]), None);
+        assert_eq!(level(&["-L", "show", "github"]), Some(LevelFilter::Info));
+        assert_eq!(level(&["-LL", "show", "github"]), Some(LevelFilter::Debug));
+        assert_eq!(
+            level(&["-LLLL", "show", "github"]),
+            Some(LevelFilter::Trace)
+        );
+    }
+
+    #[test]
+    fn verbose_log_enables_debug_records() {
+        let level = level(&["show", "github", "-LL"]).unwrap();
+        assert!(log::Level::Debug <= level);
+        assert!(log::Level::Trace > level);
+    }
+
+    #[test]
+    fn log_level_names_the_level() {
+        assert_eq!(
+            level(&["--log-level", "warn", "show", "github"]),
+            Some(LevelFilter::Warn)
+        );
+        assert!(cli()
+            .try_get_matches_from(["passlane", "-L", "--log-level", "warn", "show"])
+            .is_err());
+    }
 }
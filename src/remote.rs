@@ -0,0 +1,160 @@
+use crate::vault::entities::Error;
+use serde::Deserialize;
+use std::env;
+use std::io::Write;
+use std::path::Path;
+use std::process::{Command, Stdio};
+
+// The password of the WebDAV user or the S3 secret key. It's not saved to config.toml.
+const SECRET_ENV: &str = "PASSLANE_SYNC_PASSWORD";
+
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum RemoteKind {
+    #[default]
+    Webdav,
+    S3,
+}
+
+/// The `[sync]` table of config.toml
+#[derive(Clone, Debug, Deserialize)]
+pub struct SyncConfig {
+    /// The URL of the vault file on the server
+    pub url: String,
+    #[serde(default)]
+    pub kind: RemoteKind,
+    /// The WebDAV user or the S3 access key id
+    pub username: Option<String>,
+    /// The region of the S3 bucket, needed to sign the requests
+    pub region: Option<String>,
+}
+
+/// A copy of the encrypted vault file on another machine
+pub trait RemoteStore {
+    /// Where the remote file is, for the messages
+    fn location(&self) -> &str;
+
+    /// When the remote file was last changed as the server tells it, None if there's no remote file yet
+    fn last_modified(&self) -> Result<Option<String>, Error>;
+
+    fn download(&self, to: &Path) -> Result<(), Error>;
+
+    fn upload(&self, from: &Path) -> Result<(), Error>;
+}
+
+/// A WebDAV or S3 compatible server, used with curl like the breach check does
+pub struct CurlStore {
+    config: SyncConfig,
+    secret: Option<String>,
+}
+
+// A value in a curl config file, where backslashes and quotes are escaped
+fn curl_config_value(value: &str) -> String {
+    format!("\"{}\"", value.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+impl CurlStore {
+    pub fn new(config: &SyncConfig) -> Result<CurlStore, Error> {
+        if config.kind == RemoteKind::S3 && (config.username.is_none() || config.region.is_none()) {
+            return Err(Error::new(
+                "Syncing with S3 needs the access key id in 'username' and the 'region' of the bucket in the [sync] settings",
+            ));
+        }
+        let secret = env::var(SECRET_ENV).ok();
+        if config.username.is_some() && secret.is_none() {
+            return Err(Error::new(&format!(
+                "Set the password of the sync server to the {} environment variable",
+                SECRET_ENV
+            )));
+        }
+        Ok(CurlStore {
+            config: config.clone(),
+            secret,
+        })
+    }
+
+    // Runs curl with the arguments. The credentials are passed in a config on stdin so
+    // that other users can't see them in the process list.
+    fn curl(&self, args: &[&str]) -> Result<Vec<u8>, Error> {
+        let mut command = Command::new("curl");
+        command
+            .args(["--silent", "--show-error", "--user-agent", "passlane"])
+            .args(["--config", "-"]);
+        if let Some(region) = self.config.region.as_ref() {
+            if self.config.kind == RemoteKind::S3 {
+                command.args(["--aws-sigv4", &format!("aws:amz:{}:s3", region)]);
+            }
+        }
+        let mut child = command
+            .args(args)
+            .arg(&self.config.url)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .map_err(|e| Error::new(&format!("Failed to run curl: {}", e)))?;
+        let mut stdin = child.stdin.take().expect("stdin is piped");
+        if let (Some(username), Some(secret)) = (&self.config.username, &self.secret) {
+            writeln!(
+                stdin,
+                "user = {}",
+                curl_config_value(&format!("{}:{}", username, secret))
+            )?;
+        }
+        drop(stdin);
+        let output = child.wait_with_output()?;
+        if !output.status.success() {
+            return Err(Error::new(&format!(
+                "Failed to sync with {}: {}",
+                self.config.url,
+                String::from_utf8_lossy(&output.stderr).trim()
+            )));
+        }
+        Ok(output.stdout)
+    }
+}
+
+impl RemoteStore for CurlStore {
+    fn location(&self) -> &str {
+        &self.config.url
+    }
+
+    fn last_modified(&self) -> Result<Option<String>, Error> {
+        let response = String::from_utf8_lossy(&self.curl(&["--head"])?).to_string();
+        let status = response
+            .lines()
+            .next()
+            .and_then(|line| line.split_whitespace().nth(1))
+            .unwrap_or_default();
+        match status {
+            "404" => return Ok(None),
+            _ if status.starts_with('2') => {}
+            _ => {
+                return Err(Error::new(&format!(
+                    "Failed to sync with {}: the server answered {}",
+                    self.config.url,
+                    response.lines().next().unwrap_or_default().trim()
+                )))
+            }
+        }
+        response
+            .lines()
+            .filter_map(|line| line.split_once(':'))
+            .find(|(name, _)| name.trim().eq_ignore_ascii_case("last-modified"))
+            .map(|(_, value)| Some(value.trim().to_string()))
+            .ok_or(Error::new(&format!(
+                "{} doesn't tell when the file was changed, it can't be synced",
+                self.config.url
+            )))
+    }
+
+    fn download(&self, to: &Path) -> Result<(), Error> {
+        self.curl(&["--fail", "--output", &to.to_string_lossy()])?;
+        Ok(())
+    }
+
+    fn upload(&self, from: &Path) -> Result<(), Error> {
+        self.curl(&["--fail", "--upload-file", &from.to_string_lossy()])?;
+        Ok(())
+    }
+}
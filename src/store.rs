@@ -1,11 +1,20 @@
-use crate::vault::entities::{Credential, Error, Note, PaymentCard};
+use crate::vault::entities::{Credential, Error, Note, PaymentCard, RecoveryCodes};
 use csv::{ReaderBuilder, Writer};
-use serde::Serialize;
-use std::fs::create_dir;
-use std::fs::OpenOptions;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::env;
+#[cfg(unix)]
+use std::fs::Permissions;
+use std::fs::{self, File, OpenOptions};
+use std::fs::{create_dir_all, read_dir};
 use std::io::prelude::*;
+use std::io::{self, ErrorKind};
+#[cfg(unix)]
+use std::os::unix::fs::{OpenOptionsExt, PermissionsExt};
 use std::path::Path;
 use std::path::PathBuf;
+use std::sync::OnceLock;
+use zeroize::Zeroizing;
 
 impl From<csv::Error> for Error {
     fn from(e: csv::Error) -> Self {
@@ -40,23 +49,329 @@ pub struct CSVSecureNote {
     pub note: String,
 }
 
+#[derive(Debug, Serialize, Clone)]
+pub struct JSONCredential {
+    pub service: String,
+    pub username: String,
+    pub password: String,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub notes: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct CSVRecoveryCodes {
+    pub service: String,
+    pub username: String,
+    // JSON array of the remaining codes
+    pub codes: String,
+}
+
 fn home_dir() -> PathBuf {
     dirs::home_dir().unwrap_or_else(|| PathBuf::from("~"))
 }
 
-fn dir_path() -> PathBuf {
-    let dir_path = home_dir().join(".passlane");
-    let exists = Path::new(&dir_path).exists();
-    if !exists {
-        create_dir(&dir_path).expect("Unable to create .passlane dir");
+// Overrides the directory of all files, mainly for testing
+const PASSLANE_HOME_ENV: &str = "PASSLANE_HOME";
+
+// The files that hold settings and go to the config directory, the others go to the data directory
+const CONFIG_FILES: [&str; 7] = [
+    "config.toml",
+    ".tag_rules",
+    ".on_copy_command",
+    ".vault_path",
+    ".totp_vault_path",
+    ".keyfile_path",
+    ".totp_keyfile_path",
+];
+
+// The settings that hold paths of files which may be moved out of the legacy directory
+const PATH_FILES: [&str; 4] = [
+    ".vault_path",
+    ".totp_vault_path",
+    ".keyfile_path",
+    ".totp_keyfile_path",
+];
+
+static PROFILE: OnceLock<String> = OnceLock::new();
+static MIGRATED: OnceLock<()> = OnceLock::new();
+
+/// Uses the files of the named profile in `profiles/` instead of the ones in the passlane directory
+pub(crate) fn set_profile(name: &str) -> Result<(), Error> {
+    if name.is_empty()
+        || !name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+    {
+        return Err(Error::new(&format!(
+            "Invalid profile name '{}', use only letters, digits, '-' and '_'",
+            name
+        )));
+    }
+    PROFILE
+        .set(name.to_string())
+        .map_err(|_| Error::new("The profile is already set"))
+}
+
+/// The profile given with `--profile`, None for the default profile
+pub(crate) fn get_profile() -> Option<&'static str> {
+    PROFILE.get().map(|name| name.as_str())
+}
+
+// The directory used before the XDG directories were supported
+fn legacy_dir_path() -> PathBuf {
+    home_dir().join(".passlane")
+}
+
+fn passlane_home() -> Option<PathBuf> {
+    env::var_os(PASSLANE_HOME_ENV)
+        .filter(|value| !value.is_empty())
+        .map(PathBuf::from)
+}
+
+// The XDG spec says that relative paths in the variables are to be ignored
+fn xdg_dir_path(variable: &str) -> Option<PathBuf> {
+    env::var_os(variable)
+        .map(PathBuf::from)
+        .filter(|path| path.is_absolute())
+        .map(|path| path.join("passlane"))
+}
+
+// Left in the legacy directory after its files were moved, with the data and the config directory on
+// their own lines
+const MOVED_TO_FILE: &str = ".moved_to";
+
+// The data and the config directory the files of the legacy directory were moved to
+fn read_moved_to(legacy: &Path) -> Option<(PathBuf, PathBuf)> {
+    let content = fs::read_to_string(legacy.join(MOVED_TO_FILE)).ok()?;
+    let mut lines = content.lines().map(PathBuf::from);
+    Some((lines.next()?, lines.next()?))
+}
+
+fn write_moved_to(legacy: &Path, data: &Path, config: &Path) -> io::Result<()> {
+    create_dir_all(legacy)?;
+    fs::write(
+        legacy.join(MOVED_TO_FILE),
+        format!("{}\n{}\n", data.display(), config.display()),
+    )
+}
+
+// Resolves the data or config directory. The directory the legacy files were moved to comes before the
+// XDG variable, so that the files are found when a later run has the variable set differently or not
+// at all. The legacy directory is used when it exists, or on Linux when the XDG variable is not set.
+// Other platforms use their standard directory for new installations.
+fn resolve_base_dir(
+    home: Option<PathBuf>,
+    moved_to: Option<PathBuf>,
+    xdg_dir: Option<PathBuf>,
+    legacy: PathBuf,
+    platform_dir: Option<PathBuf>,
+) -> PathBuf {
+    if let Some(dir) = home.or(moved_to).or(xdg_dir) {
+        return dir;
+    }
+    if cfg!(target_os = "linux") || legacy.exists() {
+        return legacy;
+    }
+    platform_dir.map_or(legacy, |dir| dir.join("passlane"))
+}
+
+fn base_dir_path(
+    xdg_variable: &str,
+    moved_to: fn((PathBuf, PathBuf)) -> PathBuf,
+    platform_dir: Option<PathBuf>,
+) -> PathBuf {
+    let legacy = legacy_dir_path();
+    resolve_base_dir(
+        passlane_home(),
+        read_moved_to(&legacy).map(moved_to),
+        xdg_dir_path(xdg_variable),
+        legacy,
+        platform_dir,
+    )
+}
+
+fn data_base_dir_path() -> PathBuf {
+    base_dir_path("XDG_DATA_HOME", |(data, _)| data, dirs::data_dir())
+}
+
+fn config_base_dir_path() -> PathBuf {
+    base_dir_path("XDG_CONFIG_HOME", |(_, config)| config, dirs::config_dir())
+}
+
+// Moves the files of the legacy directory to the XDG directories
+struct Migration {
+    legacy: PathBuf,
+    data: PathBuf,
+    config: PathBuf,
+    moved: usize,
+}
+
+impl Migration {
+    fn move_file(from: &Path, to: &Path) -> std::io::Result<()> {
+        // rename doesn't work across file systems
+        if fs::rename(from, to).is_err() {
+            fs::copy(from, to)?;
+            fs::remove_file(from)?;
+        }
+        Ok(())
+    }
+
+    // Points a path setting to the new location if the file was in the legacy directory
+    fn update_path_setting(&self, setting: &Path) -> std::io::Result<()> {
+        let value = fs::read_to_string(setting)?;
+        if let Ok(relative) = Path::new(value.trim()).strip_prefix(&self.legacy) {
+            fs::write(
+                setting,
+                self.data.join(relative).to_string_lossy().as_bytes(),
+            )?;
+        }
+        Ok(())
+    }
+
+    fn migrate_dir(&mut self, relative: &Path) -> std::io::Result<()> {
+        let from = self.legacy.join(relative);
+        for entry in read_dir(&from)? {
+            let path = entry?.path();
+            let name = path.file_name().unwrap_or_default().to_os_string();
+            if relative.as_os_str().is_empty() && name == MOVED_TO_FILE {
+                continue;
+            }
+            if path.is_dir() {
+                self.migrate_dir(&relative.join(&name))?;
+                continue;
+            }
+            let is_config = CONFIG_FILES.iter().any(|file| name == *file);
+            let target_dir = if is_config { &self.config } else { &self.data }.join(relative);
+            if target_dir == from {
+                continue;
+            }
+            let target = target_dir.join(&name);
+            if target.exists() {
+                eprintln!(
+                    "Warning: not moving {}, {} already exists",
+                    path.display(),
+                    target.display()
+                );
+                continue;
+            }
+            create_dir_all(&target_dir)?;
+            Self::move_file(&path, &target)?;
+            if PATH_FILES.iter().any(|file| name == *file) {
+                self.update_path_setting(&target)?;
+            }
+            self.moved += 1;
+        }
+        // only succeeds when everything was moved
+        let _ = fs::remove_dir(&from);
+        Ok(())
+    }
+
+    // Moves the files and records where they went, for the runs that resolve the directories differently
+    fn run(&mut self) -> io::Result<()> {
+        self.migrate_dir(Path::new(""))?;
+        if read_moved_to(&self.legacy) != Some((self.data.clone(), self.config.clone())) {
+            write_moved_to(&self.legacy, &self.data, &self.config)?;
+        }
+        Ok(())
+    }
+}
+
+// Moves the files to the XDG directories the first time they are used
+fn migrate_legacy_dir() {
+    MIGRATED.get_or_init(|| {
+        let mut migration = Migration {
+            legacy: legacy_dir_path(),
+            data: data_base_dir_path(),
+            config: config_base_dir_path(),
+            moved: 0,
+        };
+        if passlane_home().is_some()
+            || !migration.legacy.is_dir()
+            || (migration.data == migration.legacy && migration.config == migration.legacy)
+        {
+            return;
+        }
+        if let Err(e) = migration.run() {
+            eprintln!(
+                "Warning: failed to move the files from {}: {}",
+                migration.legacy.display(),
+                e
+            );
+        }
+        if migration.moved > 0 {
+            eprintln!(
+                "Moved {} files from {} to {} and {}",
+                migration.moved,
+                migration.legacy.display(),
+                migration.data.display(),
+                migration.config.display()
+            );
+        }
+    });
+}
+
+// The directory of the current profile under the given base directory, created if missing
+fn profile_dir_path(base: PathBuf) -> Result<PathBuf, Error> {
+    migrate_legacy_dir();
+    let dir_path = match get_profile() {
+        Some(name) => base.join("profiles").join(name),
+        None => base,
+    };
+    create_passlane_dir(&dir_path)?;
+    Ok(dir_path)
+}
+
+// create_dir_all succeeds when another passlane process created the directory first
+fn create_passlane_dir(dir_path: &Path) -> Result<(), Error> {
+    create_dir_all(dir_path).map_err(|e| {
+        Error::new(&format!(
+            "Unable to create the passlane directory {}: {}",
+            dir_path.display(),
+            e
+        ))
+    })
+}
+
+/// The names of the profiles that have a directory in `profiles/` of the data or config directory
+pub(crate) fn list_profiles() -> Result<Vec<String>, Error> {
+    migrate_legacy_dir();
+    let mut names = Vec::new();
+    for base in [data_base_dir_path(), config_base_dir_path()] {
+        let path = base.join("profiles");
+        if !path.exists() {
+            continue;
+        }
+        names.extend(
+            read_dir(path)?
+                .filter_map(|entry| entry.ok())
+                .filter(|entry| entry.path().is_dir())
+                .filter_map(|entry| entry.file_name().into_string().ok()),
+        );
     }
-    dir_path
+    names.sort();
+    names.dedup();
+    Ok(names)
 }
 
-pub fn read_from_csv(file_path: &str) -> anyhow::Result<Vec<Credential>> {
+// The directory of the vaults and other data
+fn dir_path() -> Result<PathBuf, Error> {
+    profile_dir_path(data_base_dir_path())
+}
+
+// The directory of the settings
+fn config_dir_path() -> Result<PathBuf, Error> {
+    profile_dir_path(config_base_dir_path())
+}
+
+pub fn read_from_csv(file_path: &str, delimiter: u8) -> anyhow::Result<Vec<Credential>> {
     let path = PathBuf::from(file_path);
     let in_file = OpenOptions::new().read(true).open(path)?;
-    let mut reader = ReaderBuilder::new().has_headers(true).from_reader(in_file);
+    let mut reader = ReaderBuilder::new()
+        .has_headers(true)
+        .delimiter(delimiter)
+        .from_reader(in_file);
     let credentials = &mut Vec::new();
     for result in reader.deserialize() {
         credentials.push(result?);
@@ -64,70 +379,257 @@ pub fn read_from_csv(file_path: &str) -> anyhow::Result<Vec<Credential>> {
     Ok(credentials.clone())
 }
 
-fn read_from_file(path: &PathBuf) -> Option<String> {
-    let mut file = OpenOptions::new()
-        .read(true)
-        .write(false)
-        .create_new(false)
-        .open(&path)
-        .unwrap();
+pub fn read_recovery_codes_from_csv(file_path: &str) -> Result<Vec<RecoveryCodes>, Error> {
+    let mut reader = ReaderBuilder::new()
+        .has_headers(true)
+        .from_path(file_path)?;
+    let mut result = Vec::new();
+    for row in reader.deserialize() {
+        let row: CSVRecoveryCodes = row?;
+        let codes: Vec<String> = serde_json::from_str(&row.codes)?;
+        result.push(RecoveryCodes::new(
+            None,
+            &row.service,
+            &row.username,
+            &codes,
+            None,
+        ));
+    }
+    Ok(result)
+}
 
+fn read_from_file(path: &Path) -> Result<String, Error> {
     let mut file_content = String::new();
-    file.read_to_string(&mut file_content)
-        .expect("Unable to read master password file");
-    Some(file_content.trim().parse().unwrap())
+    OpenOptions::new()
+        .read(true)
+        .open(path)
+        .and_then(|mut file| file.read_to_string(&mut file_content))
+        .map_err(|e| Error::new(&format!("Unable to read {}: {}", path.display(), e)))?;
+    Ok(file_content.trim().to_string())
 }
 
-fn resolve_keyfile_path(path_config_file: &str) -> Option<String> {
-    let path = dir_path().join(path_config_file);
+fn resolve_keyfile_path(path_config_file: &str) -> Result<Option<String>, Error> {
+    let path = config_dir_path()?.join(path_config_file);
     if !path.exists() {
-        None
+        Ok(None)
     } else {
-        read_from_file(&path)
+        read_from_file(&path).map(Some)
     }
 }
 
-pub fn get_keyfile_path() -> Option<String> {
+pub fn get_keyfile_path() -> Result<Option<String>, Error> {
     resolve_keyfile_path(".keyfile_path")
 }
 
-pub(crate) fn get_totp_keyfile_path() -> Option<String> {
+pub(crate) fn get_totp_keyfile_path() -> Result<Option<String>, Error> {
     resolve_keyfile_path(".totp_keyfile_path")
 }
 
-fn resolve_vault_path(default_filename: &str, path_config_filename: &str) -> String {
-    let default_path = dir_path()
-        .join(default_filename)
-        .to_str()
-        .unwrap()
-        .to_string();
-    let path = dir_path().join(path_config_filename);
+fn resolve_vault_path(default_filename: &str, path_config_filename: &str) -> Result<String, Error> {
+    let path = config_dir_path()?.join(path_config_filename);
     if path.exists() {
-        return read_from_file(&path)
-            .unwrap_or(default_path)
-            .trim()
-            .to_string();
+        return read_from_file(&path);
     }
-    default_path
+    Ok(dir_path()?
+        .join(default_filename)
+        .to_string_lossy()
+        .to_string())
 }
 
 fn config_file_exists(path_config_filename: &str) -> bool {
-    dir_path().join(path_config_filename).exists()
+    config_dir_path().is_ok_and(|dir| dir.join(path_config_filename).exists())
 }
 
-pub(crate) fn get_vault_path() -> String {
+pub(crate) fn get_vault_path() -> Result<String, Error> {
     resolve_vault_path("store.kdbx", ".vault_path")
 }
 
-pub(crate) fn get_totp_vault_path() -> String {
+pub(crate) fn get_totp_vault_path() -> Result<String, Error> {
     resolve_vault_path("totp.kdbx", ".totp_vault_path")
 }
 
+// Matches a service against a pattern where `*` stands for any characters, ignoring case
+fn service_matches_pattern(service: &str, pattern: &str) -> bool {
+    let regex = format!("(?i)^{}$", regex::escape(pattern).replace("\\*", ".*"));
+    Regex::new(&regex)
+        .map(|re| re.is_match(service))
+        .unwrap_or(false)
+}
+
+/// Returns the tags that the rules in `~/.passlane/.tag_rules` assign to the given service.
+/// Each line of the rules file has a service pattern and a tag, for example `*bank* banking`.
+pub(crate) fn tags_for_service(service: &str) -> Result<Vec<String>, Error> {
+    let path = config_dir_path()?.join(".tag_rules");
+    if !path.exists() {
+        return Ok(vec![]);
+    }
+    let rules = read_from_file(&path)?;
+    let mut tags: Vec<String> = rules
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .filter_map(|line| line.rsplit_once(char::is_whitespace))
+        .filter(|(pattern, _)| service_matches_pattern(service, pattern.trim()))
+        .map(|(_, tag)| tag.to_string())
+        .collect();
+    tags.sort();
+    tags.dedup();
+    Ok(tags)
+}
+
+/// The command in `~/.passlane/.on_copy_command` that is run after a password has been copied
+pub(crate) fn get_on_copy_command() -> Result<Option<String>, Error> {
+    let path = config_dir_path()?.join(".on_copy_command");
+    if !path.exists() {
+        return Ok(None);
+    }
+    Ok(Some(read_from_file(&path)?).filter(|command| !command.is_empty()))
+}
+
+/// The contents of `~/.passlane/config.toml`, if it exists
+pub(crate) fn read_config() -> Result<Option<String>, Error> {
+    let path = config_dir_path()?.join("config.toml");
+    if !path.exists() {
+        return Ok(None);
+    }
+    read_from_file(&path).map(Some)
+}
+
+/// The master password on the first line of the file given with `--master-password-file`.
+/// Warns when other users could read the file.
+pub(crate) fn read_master_password_file(file_path: &str) -> Result<Zeroizing<String>, Error> {
+    read_master_password_file_with(file_path, &mut io::stderr())
+}
+
+fn read_master_password_file_with(
+    file_path: &str,
+    warnings: &mut dyn Write,
+) -> Result<Zeroizing<String>, Error> {
+    let path = Path::new(file_path);
+    let content = Zeroizing::new(read_from_file(path)?);
+    #[cfg(unix)]
+    if fs::metadata(path)?.permissions().mode() & 0o077 != 0 {
+        writeln!(
+            warnings,
+            "Warning: other users could read the master password file '{}', restrict it with `chmod 600`",
+            file_path
+        )?;
+    }
+    let password = content.lines().next().unwrap_or_default().trim();
+    if password.is_empty() {
+        return Err(Error::new(&format!(
+            "The master password file '{}' is empty",
+            file_path
+        )));
+    }
+    Ok(Zeroizing::new(password.to_string()))
+}
+
+// The wrong master passwords are counted separately for the vault and the TOTP vault
+const UNLOCK_FAILURES_FILE: &str = ".unlock_failures";
+const TOTP_UNLOCK_FAILURES_FILE: &str = ".totp_unlock_failures";
+
+fn unlock_failures_path(totp: bool) -> Result<PathBuf, Error> {
+    Ok(dir_path()?.join(if totp {
+        TOTP_UNLOCK_FAILURES_FILE
+    } else {
+        UNLOCK_FAILURES_FILE
+    }))
+}
+
+/// The number of consecutive wrong master passwords of the vault and the Unix time of the last one
+pub(crate) fn get_unlock_failures(totp: bool) -> Option<(u32, u64)> {
+    let path = unlock_failures_path(totp).ok()?;
+    if !path.exists() {
+        return None;
+    }
+    let content = read_from_file(&path).ok()?;
+    let (count, timestamp) = content.split_once(' ')?;
+    Some((count.parse().ok()?, timestamp.parse().ok()?))
+}
+
+pub(crate) fn save_unlock_failures(totp: bool, count: u32, timestamp: u64) -> Result<(), Error> {
+    fs::write(
+        unlock_failures_path(totp)?,
+        format!("{} {}", count, timestamp),
+    )?;
+    Ok(())
+}
+
+pub(crate) fn clear_unlock_failures(totp: bool) -> Result<(), Error> {
+    remove_if_exists(&unlock_failures_path(totp)?)
+}
+
+// Unix times when the keychain passwords saved with `unlock --for` expire
+const UNLOCK_EXPIRY_FILE: &str = ".unlock_expires";
+const TOTP_UNLOCK_EXPIRY_FILE: &str = ".totp_unlock_expires";
+
+fn unlock_expiry_path(totp: bool) -> Result<PathBuf, Error> {
+    Ok(dir_path()?.join(if totp {
+        TOTP_UNLOCK_EXPIRY_FILE
+    } else {
+        UNLOCK_EXPIRY_FILE
+    }))
+}
+
+/// The Unix time when the unlocked vault should be locked again, None if it stays unlocked
+pub(crate) fn get_unlock_expiry(totp: bool) -> Option<u64> {
+    let path = unlock_expiry_path(totp).ok()?;
+    if !path.exists() {
+        return None;
+    }
+    read_from_file(&path).ok()?.trim().parse().ok()
+}
+
+pub(crate) fn save_unlock_expiry(totp: bool, timestamp: u64) -> Result<(), Error> {
+    fs::write(unlock_expiry_path(totp)?, timestamp.to_string())?;
+    Ok(())
+}
+
+pub(crate) fn clear_unlock_expiry(totp: bool) -> Result<(), Error> {
+    remove_if_exists(&unlock_expiry_path(totp)?)
+}
+
+// Removes a file, a file that another passlane process removed first is not an error
+fn remove_if_exists(path: &Path) -> Result<(), Error> {
+    match fs::remove_file(path) {
+        Err(e) if e.kind() != ErrorKind::NotFound => Err(e.into()),
+        _ => Ok(()),
+    }
+}
+
+// The state of the vault after the last sync: the URL of the remote copy, the modification time of
+// the local file in milliseconds and the Last-Modified time of the remote file
+const SYNC_STATE_FILE: &str = ".sync_state";
+
+/// The state of the last sync with the given URL, None if the vault has not been synced with it
+pub(crate) fn get_sync_state(url: &str) -> Option<(u128, String)> {
+    let path = dir_path().ok()?.join(SYNC_STATE_FILE);
+    let content = read_from_file(&path).ok()?;
+    let mut lines = content.lines();
+    if lines.next()? != url {
+        return None;
+    }
+    Some((lines.next()?.parse().ok()?, lines.next()?.to_string()))
+}
+
+pub(crate) fn save_sync_state(
+    url: &str,
+    local_modified: u128,
+    remote_modified: &str,
+) -> Result<(), Error> {
+    fs::write(
+        dir_path()?.join(SYNC_STATE_FILE),
+        format!("{}\n{}\n{}", url, local_modified, remote_modified),
+    )?;
+    Ok(())
+}
+
 pub(crate) fn write_credentials_to_csv(
     file_path: &str,
     creds: &Vec<Credential>,
 ) -> Result<i64, Error> {
-    let mut wtr = Writer::from_path(file_path)?;
+    let mut wtr = Writer::from_writer(create_private_file(file_path)?);
     for cred in creds {
         wtr.serialize(cred)?;
     }
@@ -135,11 +637,29 @@ pub(crate) fn write_credentials_to_csv(
     Ok(creds.len() as i64)
 }
 
+pub(crate) fn write_credentials_to_json(
+    file_path: &str,
+    creds: &[Credential],
+) -> Result<i64, Error> {
+    let json_creds: Vec<JSONCredential> = creds
+        .iter()
+        .map(|c| JSONCredential {
+            service: c.service().to_string(),
+            username: c.username().to_string(),
+            password: c.password().to_string(),
+            tags: c.tags().to_vec(),
+            notes: c.notes().map(String::from),
+        })
+        .collect();
+    serde_json::to_writer_pretty(create_private_file(file_path)?, &json_creds)?;
+    Ok(creds.len() as i64)
+}
+
 pub(crate) fn write_payment_cards_to_csv(
     file_path: &str,
     cards: &Vec<PaymentCard>,
 ) -> Result<i64, Error> {
-    let mut wtr = Writer::from_path(file_path)?;
+    let mut wtr = Writer::from_writer(create_private_file(file_path)?);
     for card in cards {
         wtr.serialize(CSVPaymentCard {
             name: String::from(card.name()),
@@ -162,7 +682,7 @@ pub(crate) fn write_payment_cards_to_csv(
 }
 
 pub(crate) fn write_secure_notes_to_csv(file_path: &str, notes: &Vec<Note>) -> Result<i64, Error> {
-    let mut wtr = Writer::from_path(file_path)?;
+    let mut wtr = Writer::from_writer(create_private_file(file_path)?);
     for note in notes {
         wtr.serialize(CSVSecureNote {
             title: note.title().to_string(),
@@ -173,11 +693,71 @@ pub(crate) fn write_secure_notes_to_csv(file_path: &str, notes: &Vec<Note>) -> R
     Ok(notes.len() as i64)
 }
 
+pub(crate) fn write_recovery_codes_to_csv(
+    file_path: &str,
+    recovery_codes: &Vec<RecoveryCodes>,
+) -> Result<i64, Error> {
+    let mut wtr = Writer::from_writer(create_private_file(file_path)?);
+    for codes in recovery_codes {
+        wtr.serialize(CSVRecoveryCodes {
+            service: codes.service().to_string(),
+            username: codes.username().to_string(),
+            codes: serde_json::to_string(codes.codes())?,
+        })?;
+    }
+    wtr.flush()?;
+    Ok(recovery_codes.len() as i64)
+}
+
+/// Opens the file for writing so that only the current user can read and write it. An existing
+/// file that other users can read is restricted too, with a warning.
+pub(crate) fn create_private_file(file_path: &str) -> Result<File, Error> {
+    Ok(open_private_file(file_path, false)?)
+}
+
+// With `new_only` the file is created atomically and an existing file is an AlreadyExists error,
+// so that two processes can't both think they created it
+fn open_private_file(file_path: &str, new_only: bool) -> io::Result<File> {
+    let mut options = OpenOptions::new();
+    options.write(true);
+    if new_only {
+        options.create_new(true);
+    } else {
+        options.create(true).truncate(true);
+    }
+    #[cfg(unix)]
+    options.mode(0o600);
+    let file = options.open(file_path)?;
+    // the mode is only applied to new files
+    #[cfg(unix)]
+    if file.metadata()?.permissions().mode() & 0o077 != 0 {
+        eprintln!(
+            "Warning: other users could read '{}', restricting it to your user",
+            file_path
+        );
+        file.set_permissions(Permissions::from_mode(0o600))?;
+    }
+    Ok(file)
+}
+
+/// Writes the content to a file that only the current user can read and write.
+/// An existing file is only overwritten when `force` is set.
+pub(crate) fn write_private_file(file_path: &str, content: &str, force: bool) -> Result<(), Error> {
+    let mut file = open_private_file(file_path, !force).map_err(|e| match e.kind() {
+        ErrorKind::AlreadyExists => Error::new(&format!(
+            "File '{}' already exists, use --force to overwrite it",
+            file_path
+        )),
+        _ => e.into(),
+    })?;
+    file.write_all(content.as_bytes())?;
+    Ok(())
+}
+
 pub fn save_config_path(config_file: &str, path: &str) -> Result<(), Error> {
-    let config_path = dir_path().join(config_file);
-    let exists = config_path.exists();
+    let config_path = config_dir_path()?.join(config_file);
     let mut file = OpenOptions::new()
-        .create(!exists)
+        .create(true)
         .write(true)
         .truncate(true)
         .open(config_path)?;
@@ -208,3 +788,160 @@ pub fn has_totp_vault_path() -> bool {
 pub fn has_keyfile_path() -> bool {
     config_file_exists(".keyfile_path")
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::{test_dir, test_file};
+
+    #[test]
+    fn concurrent_runs_create_the_same_directory() {
+        let dir = test_dir("concurrent-create").join("profiles").join("work");
+        let threads: Vec<_> = (0..8)
+            .map(|_| {
+                let dir = dir.clone();
+                std::thread::spawn(move || create_passlane_dir(&dir).is_ok())
+            })
+            .collect();
+        for thread in threads {
+            assert!(thread.join().unwrap());
+        }
+        assert!(dir.is_dir());
+    }
+
+    #[test]
+    fn removing_a_missing_file_succeeds() {
+        let path = test_dir("remove-missing").join(".unlock_expiry");
+        assert!(remove_if_exists(&path).is_ok());
+        fs::write(&path, "1").unwrap();
+        assert!(remove_if_exists(&path).is_ok());
+        assert!(!path.exists());
+    }
+
+    #[test]
+    fn xdg_variable_redirects_the_directory() {
+        let dir = resolve_base_dir(
+            None,
+            None,
+            Some(PathBuf::from("/xdg/data/passlane")),
+            PathBuf::from("/home/user/.passlane"),
+            None,
+        );
+        assert_eq!(dir, PathBuf::from("/xdg/data/passlane"));
+    }
+
+    #[test]
+    fn passlane_home_overrides_everything() {
+        let dir = resolve_base_dir(
+            Some(PathBuf::from("/passlane/home")),
+            Some(PathBuf::from("/moved/data")),
+            Some(PathBuf::from("/xdg/data/passlane")),
+            PathBuf::from("/home/user/.passlane"),
+            None,
+        );
+        assert_eq!(dir, PathBuf::from("/passlane/home"));
+    }
+
+    #[test]
+    fn migration_moves_the_files_and_records_the_directories() {
+        let root = test_dir("migration");
+        let legacy = root.join("legacy");
+        create_dir_all(&legacy).unwrap();
+        fs::write(legacy.join("store.kdbx"), "vault").unwrap();
+        fs::write(legacy.join("config.toml"), "").unwrap();
+        let mut migration = Migration {
+            legacy: legacy.clone(),
+            data: root.join("data"),
+            config: root.join("config"),
+            moved: 0,
+        };
+        migration.run().unwrap();
+
+        assert_eq!(migration.moved, 2);
+        assert!(root.join("data/store.kdbx").exists());
+        assert!(root.join("config/config.toml").exists());
+        assert!(!legacy.join("store.kdbx").exists());
+        assert_eq!(
+            read_moved_to(&legacy),
+            Some((root.join("data"), root.join("config")))
+        );
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn moved_directory_is_used_when_the_xdg_variable_is_unset() {
+        let legacy = test_dir("moved-unset");
+        write_moved_to(&legacy, Path::new("/xdg/data"), Path::new("/xdg/config")).unwrap();
+        let (data, _) = read_moved_to(&legacy).unwrap();
+        let dir = resolve_base_dir(None, Some(data), None, legacy.clone(), None);
+        assert_eq!(dir, PathBuf::from("/xdg/data"));
+        fs::remove_dir_all(&legacy).unwrap();
+    }
+
+    #[test]
+    fn moved_directory_is_used_when_the_xdg_variable_changes() {
+        let legacy = test_dir("moved-changed");
+        write_moved_to(&legacy, Path::new("/xdg/data"), Path::new("/xdg/config")).unwrap();
+        let (_, config) = read_moved_to(&legacy).unwrap();
+        let dir = resolve_base_dir(
+            None,
+            Some(config),
+            Some(PathBuf::from("/other/config/passlane")),
+            legacy.clone(),
+            None,
+        );
+        assert_eq!(dir, PathBuf::from("/xdg/config"));
+        fs::remove_dir_all(&legacy).unwrap();
+    }
+
+    #[test]
+    fn second_migration_keeps_the_record() {
+        let root = test_dir("migration-twice");
+        let legacy = root.join("legacy");
+        create_dir_all(&legacy).unwrap();
+        fs::write(legacy.join("store.kdbx"), "vault").unwrap();
+        let mut migration = Migration {
+            legacy: legacy.clone(),
+            data: root.join("data"),
+            config: root.join("config"),
+            moved: 0,
+        };
+        migration.run().unwrap();
+        migration.run().unwrap();
+
+        assert_eq!(migration.moved, 1);
+        assert!(legacy.join(MOVED_TO_FILE).exists());
+        assert!(!root.join("data").join(MOVED_TO_FILE).exists());
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    fn read_with_mode(name: &str, content: &str, mode: u32) -> (Result<String, Error>, String) {
+        let path = test_file(name, content);
+        fs::set_permissions(&path, Permissions::from_mode(mode)).unwrap();
+        let mut warnings = Vec::new();
+        let password = read_master_password_file_with(&path, &mut warnings)
+            .map(|password| password.to_string());
+        (password, String::from_utf8(warnings).unwrap())
+    }
+
+    #[test]
+    fn master_password_is_the_first_line_of_the_file() {
+        let (password, warnings) =
+            read_with_mode("master-first-line", "  secret \nsecond\n", 0o600);
+        assert_eq!(password.unwrap(), "secret");
+        assert_eq!(warnings, "");
+    }
+
+    #[test]
+    fn empty_master_password_file_is_an_error() {
+        let (password, _) = read_with_mode("master-empty", "", 0o600);
+        assert!(password.unwrap_err().message.contains("is empty"));
+    }
+
+    #[test]
+    fn master_password_file_readable_by_others_is_warned_about() {
+        let (password, warnings) = read_with_mode("master-world-readable", "secret\n", 0o644);
+        assert_eq!(password.unwrap(), "secret");
+        assert!(warnings.starts_with("Warning: other users could read the master password file"));
+    }
+}
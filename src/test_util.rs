@@ -0,0 +1,50 @@
+use crate::vault::entities::{Address, Credential, Expiry, Note, PaymentCard};
+use crate::vault::keepass_vault::KeepassVault;
+use crate::vault::vault_trait::{NoteVault, PasswordVault, PaymentVault};
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+
+/// An empty directory for one test under the system temp directory
+pub(crate) fn test_dir(name: &str) -> PathBuf {
+    let dir = env::temp_dir().join(format!("passlane-test-{}-{}", std::process::id(), name));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+/// Writes the content to a file in a new test directory and returns its path
+pub(crate) fn test_file(name: &str, content: &str) -> String {
+    let path = test_dir(name).join(name);
+    fs::write(&path, content).unwrap();
+    path.to_string_lossy().to_string()
+}
+
+/// A new vault with one weak credential for `real.com`, a secure note and a payment card
+pub(crate) fn mixed_vault(name: &str) -> KeepassVault {
+    let path = test_dir(name).join("store.kdbx");
+    let mut vault = KeepassVault::new(&path.to_string_lossy(), "test", None).unwrap();
+    let credential = Credential::new(None, "password", "real.com", "alice", None, &[], None);
+    vault.save_one_credential(credential).unwrap();
+    vault
+        .save_note(&Note::new(None, "MyNoteTitle", "secret note body", None))
+        .unwrap();
+    let expiry = Expiry {
+        month: 12,
+        year: 2030,
+    };
+    let address = Address::new(None, "Main Street 1", "Helsinki", "Finland", None, "00100");
+    let card = PaymentCard::new(
+        None,
+        "Visa",
+        "Alice",
+        "4111111111111111",
+        "123",
+        expiry,
+        None,
+        Some(&address),
+        None,
+    );
+    vault.save_payment(card).unwrap();
+    vault
+}
@@ -1,3 +1,4 @@
+use std::io::{self, BufRead, IsTerminal};
 use std::path::Path;
 
 use rustyline::completion::{Completer, Pair};
@@ -8,8 +9,9 @@ use rustyline::validate::Validator;
 use rustyline::{Config, Editor, Result as RustylineResult};
 use rustyline_derive::Helper;
 
-use crate::vault::entities::{Address, Credential, Expiry, Note, PaymentCard, Totp};
-use inquire::{Confirm, CustomType, Password, Select, Text};
+use crate::vault::entities::{Address, Credential, Expiry, Note, PaymentCard, RecoveryCodes, Totp};
+use inquire::{Confirm, CustomType, CustomUserError, Password, Select, Text};
+use url::Url;
 
 #[derive(Helper)]
 struct MultilineHelper {
@@ -171,13 +173,125 @@ pub fn ask_number(question: &str) -> u64 {
         .unwrap()
 }
 
-pub fn ask_credentials(password: &str) -> Credential {
-    let service = ask("Enter URL or service");
-    let username = ask("Enter username");
-    Credential::new(None, password, &service, &username, None)
+/// Asks the service and username of a new credential, except the ones that are given
+pub fn ask_credentials<F>(
+    password: &str,
+    service: Option<String>,
+    username: Option<String>,
+    no_username: bool,
+    accept_invalid_url: bool,
+    usernames_for_service: F,
+) -> Credential
+where
+    F: Fn(&str) -> Vec<String>,
+{
+    let service = service.unwrap_or_else(|| ask_service(accept_invalid_url));
+    let username = if no_username {
+        String::new()
+    } else {
+        username.unwrap_or_else(|| ask_username(usernames_for_service(&service)))
+    };
+    Credential::new(None, password, &service, &username, None, &[], None)
+        .with_no_username(no_username)
+}
+
+// Top level domains that are most likely typos of common ones
+const TLD_TYPOS: [&str; 8] = ["con", "cmo", "ocm", "vom", "comm", "ogr", "nte", "rog"];
+
+fn looks_like_url(service: &str) -> bool {
+    service.contains("://") || (service.contains('.') && !service.contains(char::is_whitespace))
+}
+
+fn normalize_url(service: &str) -> Result<String, String> {
+    let with_scheme = if service.contains("://") {
+        service.to_string()
+    } else {
+        format!("https://{}", service)
+    };
+    let url = Url::parse(&with_scheme).map_err(|e| e.to_string())?;
+    if url.host_str().is_none() {
+        return Err("the URL has no host".to_string());
+    }
+    let normalized = url.to_string();
+    // drop the slash that the parser adds to URLs without a path
+    if url.path() == "/" && url.query().is_none() && url.fragment().is_none() {
+        Ok(normalized.trim_end_matches('/').to_string())
+    } else {
+        Ok(normalized)
+    }
+}
+
+fn url_typo_warning(url: &str) -> Option<String> {
+    let url = Url::parse(url).ok()?;
+    let host = url.host_str()?;
+    if url.scheme() != "https" && url.scheme() != "http" {
+        return Some(format!("the scheme '{}' is unusual", url.scheme()));
+    }
+    if host.contains("..") || host.starts_with("ww.") || host.starts_with("wwww.") {
+        return Some(format!("the host '{}' looks mistyped", host));
+    }
+    let tld = host.rsplit('.').next().unwrap_or("");
+    if TLD_TYPOS.contains(&tld) {
+        return Some(format!("the domain ending '.{}' looks mistyped", tld));
+    }
+    None
+}
+
+/// Normalizes the service if it's a URL. An invalid URL is an error unless `accept_invalid_url` is set.
+pub fn validate_service(service: &str, accept_invalid_url: bool) -> Result<String, String> {
+    if !looks_like_url(service) {
+        return Ok(service.to_string());
+    }
+    match normalize_url(service) {
+        Ok(url) => {
+            if let Some(warning) = url_typo_warning(&url) {
+                eprintln!("Warning: {}", warning);
+            }
+            Ok(url)
+        }
+        Err(e) if accept_invalid_url => {
+            eprintln!("Warning: '{}' is not a valid URL: {}", service, e);
+            Ok(service.to_string())
+        }
+        Err(e) => Err(format!("'{}' is not a valid URL: {}", service, e)),
+    }
+}
+
+fn ask_service(accept_invalid_url: bool) -> String {
+    loop {
+        let service = ask("Enter URL or service");
+        match validate_service(&service, accept_invalid_url) {
+            Ok(service) => return service,
+            Err(e) => println!("{}. Please try again, or use --force to save it anyway", e),
+        }
+    }
+}
+
+fn ask_username(previous_usernames: Vec<String>) -> String {
+    if previous_usernames.is_empty() {
+        return ask("Enter username");
+    }
+    let suggest = move |input: &str| -> Result<Vec<String>, CustomUserError> {
+        Ok(previous_usernames
+            .iter()
+            .filter(|u| u.to_lowercase().contains(&input.to_lowercase()))
+            .cloned()
+            .collect())
+    };
+    Text::new("Enter username")
+        .with_autocomplete(suggest)
+        .with_help_message(
+            "Usernames already saved for this service are suggested, use arrows to pick one",
+        )
+        .prompt()
+        .unwrap()
 }
 
-pub(crate) fn ask_modified_credential<'a>(the_match: &'a Credential) -> Credential {
+/// Asks the new values of the credential. The given tags replace the current ones.
+pub(crate) fn ask_modified_credential(
+    the_match: &Credential,
+    tags: Option<&[String]>,
+) -> Credential {
     let service = ask_with_initial(
         "Enter URL or service",
         Some(the_match.service()),
@@ -189,6 +303,12 @@ pub(crate) fn ask_modified_credential<'a>(the_match: &'a Credential) -> Credenti
         Some("Press enter and leave empty to keep the current value shown in parantheses"),
     );
     let password = ask_new_password("Enter new password");
+    let notes = ask_with_initial_optional(
+        "Enter notes",
+        the_match.notes(),
+        Some("Optional. Press enter to keep the current notes"),
+        true,
+    );
 
     Credential::new(
         Some(the_match.uuid()),
@@ -196,7 +316,11 @@ pub(crate) fn ask_modified_credential<'a>(the_match: &'a Credential) -> Credenti
         &service,
         &username,
         None,
+        tags.unwrap_or(the_match.tags()),
+        notes.as_deref(),
     )
+    // typing a username gives one to a credential that was saved without
+    .with_no_username(!the_match.has_username() && username.is_empty())
 }
 
 pub(crate) fn ask_modified_address(address: &Address) -> Address {
@@ -305,12 +429,39 @@ pub(crate) fn ask_modified_totp<'a>(the_match: &'a Totp) -> Totp {
     )
 }
 
-pub fn ask_master_password(question: Option<&str>) -> String {
-    if let Some(q) = question {
-        ask_password(q, None)
-    } else {
-        ask_password("Please enter master password", None)
+/// True when stdin is not a terminal, so passwords are read from it instead of asked
+pub(crate) fn stdin_is_piped() -> bool {
+    !io::stdin().is_terminal()
+}
+
+/// The first line of stdin when it's not a terminal and a password is piped in, e.g. `echo pw | passlane show x`.
+/// None when stdin is a terminal, or when nothing or only an empty line is left in it.
+pub(crate) fn read_piped_password() -> Option<String> {
+    if !stdin_is_piped() {
+        return None;
+    }
+    let mut line = String::new();
+    match io::stdin().lock().read_line(&mut line) {
+        Ok(0) | Err(_) => return None,
+        Ok(_) => {}
+    }
+    let password = line.trim_end_matches(['\r', '\n']);
+    if password.trim().is_empty() {
+        return None;
     }
+    Some(password.to_string())
+}
+
+/// Asks the master password, or reads it from stdin when stdin is not a terminal. None when
+/// no master password was piped, because there's no terminal to ask it from.
+pub fn ask_master_password(question: Option<&str>) -> Option<String> {
+    if stdin_is_piped() {
+        return read_piped_password();
+    }
+    Some(ask_password(
+        question.unwrap_or("Please enter master password"),
+        None,
+    ))
 }
 
 pub fn ask_new_master_password() -> String {
@@ -324,11 +475,27 @@ pub fn ask_new_master_password() -> String {
     }
 }
 
-pub(crate) fn ask_totp_master_password() -> String {
-    ask_password(
+pub(crate) fn ask_new_backup_passphrase() -> String {
+    let passphrase = ask_password(
+        "Please enter a passphrase for the key backup",
+        Some("Use another passphrase than the master password"),
+    );
+    if passphrase.is_empty() {
+        println!("The passphrase can't be empty, please try again");
+        ask_new_backup_passphrase()
+    } else {
+        passphrase
+    }
+}
+
+pub(crate) fn ask_totp_master_password() -> Option<String> {
+    if stdin_is_piped() {
+        return read_piped_password();
+    }
+    Some(ask_password(
         "Please enter master password of the One Time Passwords vault",
         None,
-    )
+    ))
 }
 
 pub fn ask_index(
@@ -355,6 +522,29 @@ pub fn ask_index(
     }
 }
 
+// Below this many matches the row number prompt is quick enough
+const MIN_MATCHES_FOR_PICKER: usize = 6;
+
+/// Whether a credential can be picked from a list that is narrowed by typing, instead of
+/// entering its row number. It needs a terminal and is only used when there are many matches.
+pub(crate) fn can_pick(match_count: usize) -> bool {
+    match_count >= MIN_MATCHES_FOR_PICKER && io::stdin().is_terminal() && io::stdout().is_terminal()
+}
+
+/// Lets the user pick one of the credentials with a fuzzy search and returns its index
+pub(crate) fn pick_credential(question: &str, credentials: &[Credential]) -> Result<usize, String> {
+    let options: Vec<String> = credentials
+        .iter()
+        .enumerate()
+        .map(|(index, c)| format!("{}: {} ({})", index, c.service(), c.username()))
+        .collect();
+    Select::new(question, options)
+        .with_help_message("Type to filter, ↑↓ to move, enter to select, esc to quit")
+        .raw_prompt()
+        .map(|choice| choice.index)
+        .map_err(|_| String::from("Quitting"))
+}
+
 fn ask_address() -> Address {
     println!("Enter billing address");
     let street = ask("Enter street address");
@@ -408,6 +598,15 @@ pub(crate) fn ask_note_info() -> Note {
     Note::new(None, &title, &content, None)
 }
 
+pub(crate) fn ask_recovery_codes_info() -> RecoveryCodes {
+    let service = ask("Enter URL or service");
+    let username = ask("Enter username");
+    let codes = ask_multiline_with_initial("Enter the recovery codes, one per line", None);
+    let codes: Vec<String> = codes.lines().map(String::from).collect();
+
+    RecoveryCodes::new(None, &service, &username, &codes, None)
+}
+
 fn format_totp_url(
     label: &str,
     secret: &str,
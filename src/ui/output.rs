@@ -1,13 +1,30 @@
+use chrono::{DateTime, Utc};
 use comfy_table::*;
+use serde::Serialize;
 use std::cmp::min;
+use std::io::IsTerminal;
 
-use crate::vault::entities::{Credential, Note, PaymentCard, Totp};
+use crate::crypto::estimate_strength;
+use crate::vault::entities::{
+    Credential, CredentialDiff, DiffStatus, Error, Note, PaymentCard, RecoveryCodes, Totp,
+};
+
+const MAX_NOTES_WIDTH: usize = 30;
 
 pub fn show_credentials_table(credentials: &[Credential], show_password: bool) {
     let mut table = Table::new();
     let header_cell = |label: String| -> Cell { Cell::new(label).fg(Color::Green) };
     let headers = if show_password {
-        vec!["", "Service", "Username/email", "Password", "Modified"]
+        vec![
+            "",
+            "Service",
+            "Username/email",
+            "Password",
+            "Strength",
+            "Tags",
+            "Notes",
+            "Modified",
+        ]
     } else {
         vec!["", "Service", "Username/email", "Modified"]
     };
@@ -23,15 +40,18 @@ pub fn show_credentials_table(credentials: &[Credential], show_password: bool) {
             vec![
                 Cell::new(index.to_string()).fg(Color::Yellow),
                 Cell::new(service[..min(service.len(), 30)].to_string()),
-                Cell::new(String::from(creds.username())),
+                username_cell(creds),
                 Cell::new(String::from(creds.password())),
+                Cell::new(estimate_strength(creds.password()).to_string()),
+                Cell::new(creds.tags().join(", ")),
+                notes_cell(creds),
                 Cell::new(creds.last_modified().format("%d.%m.%Y %H:%M").to_string()),
             ]
         } else {
             vec![
                 Cell::new(index.to_string()).fg(Color::Yellow),
                 Cell::new(service[..min(service.len(), 30)].to_string()),
-                Cell::new(String::from(creds.username())),
+                username_cell(creds),
                 Cell::new(creds.last_modified().format("%d.%m.%Y %H:%M").to_string()),
             ]
         };
@@ -40,6 +60,25 @@ pub fn show_credentials_table(credentials: &[Credential], show_password: bool) {
     println!("{table}");
 }
 
+// Long notes are cut so that the table stays readable, `show --json` prints them in full
+fn notes_cell(creds: &Credential) -> Cell {
+    let notes = creds.notes().unwrap_or("");
+    if notes.chars().count() > MAX_NOTES_WIDTH {
+        let start: String = notes.chars().take(MAX_NOTES_WIDTH).collect();
+        Cell::new(format!("{}...", start))
+    } else {
+        Cell::new(notes)
+    }
+}
+
+fn username_cell(creds: &Credential) -> Cell {
+    if creds.has_username() {
+        Cell::new(String::from(creds.username()))
+    } else {
+        Cell::new("—")
+    }
+}
+
 fn header_cell(label: String) -> Cell {
     Cell::new(label).fg(Color::Green)
 }
@@ -189,3 +228,202 @@ pub(crate) fn show_totp_table(totps: &[Totp]) {
     }
     println!("{table}");
 }
+
+pub(crate) fn show_recovery_codes_table(recovery_codes: &[RecoveryCodes]) {
+    let mut table = Table::new();
+    table.set_header(
+        ["", "Service", "Username/email", "Codes left", "Modified"]
+            .iter()
+            .map(|&h| header_cell(String::from(h)))
+            .collect::<Vec<Cell>>(),
+    );
+    for (index, codes) in recovery_codes.iter().enumerate() {
+        table.add_row(vec![
+            Cell::new(index.to_string()).fg(Color::Yellow),
+            Cell::new(codes.service()),
+            Cell::new(codes.username()),
+            Cell::new(codes.codes().len().to_string()),
+            Cell::new(codes.last_modified().format("%d.%m.%Y %H:%M").to_string()),
+        ]);
+    }
+    println!("{table}");
+}
+
+pub(crate) fn show_recovery_codes(recovery_codes: &RecoveryCodes) {
+    let mut table = Table::new();
+    table.set_header(vec![
+        header_cell("".to_string()),
+        header_cell("Recovery code".to_string()),
+    ]);
+    for (index, code) in recovery_codes.codes().iter().enumerate() {
+        table.add_row(vec![
+            Cell::new(index.to_string()).fg(Color::Yellow),
+            Cell::new(code),
+        ]);
+    }
+    println!("{table}");
+}
+
+pub(crate) fn show_diff_table(diffs: &[CredentialDiff]) {
+    let mut table = Table::new();
+    table.set_header(
+        ["Service", "Username/email", "Difference"]
+            .iter()
+            .map(|&h| header_cell(String::from(h)))
+            .collect::<Vec<Cell>>(),
+    );
+    for diff in diffs {
+        let color = match diff.status {
+            DiffStatus::OnlyInVault => Color::Cyan,
+            DiffStatus::OnlyInFile => Color::Yellow,
+            DiffStatus::PasswordDiffers => Color::Red,
+        };
+        table.add_row(vec![
+            Cell::new(&diff.service),
+            Cell::new(&diff.username),
+            Cell::new(diff.status.to_string()).fg(color),
+        ]);
+    }
+    println!("{table}");
+}
+
+pub(crate) fn show_breached_table(breached: &[(Credential, u64)]) {
+    let mut table = Table::new();
+    table.set_header(
+        ["Service", "Username/email", "Seen in breaches"]
+            .iter()
+            .map(|&h| header_cell(String::from(h)))
+            .collect::<Vec<Cell>>(),
+    );
+    for (creds, count) in breached {
+        table.add_row(vec![
+            Cell::new(creds.service()),
+            username_cell(creds),
+            Cell::new(format!("{} times", count)).fg(Color::Red),
+        ]);
+    }
+    println!("{table}");
+}
+
+// A credential in the JSON output, the password is only included when asked for
+#[derive(Serialize)]
+struct CredentialJson<'a> {
+    service: &'a str,
+    username: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    password: Option<&'a str>,
+    tags: &'a [String],
+    #[serde(skip_serializing_if = "Option::is_none")]
+    notes: Option<&'a str>,
+    last_modified: &'a DateTime<Utc>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    created: Option<&'a DateTime<Utc>>,
+}
+
+impl<'a> CredentialJson<'a> {
+    fn new(credential: &'a Credential, show_password: bool) -> Self {
+        CredentialJson {
+            service: credential.service(),
+            username: credential.username(),
+            password: Some(credential.password()).filter(|_| show_password),
+            tags: credential.tags(),
+            notes: credential.notes(),
+            last_modified: credential.last_modified(),
+            created: credential.created(),
+        }
+    }
+}
+
+/// Renders the credential as a JSON object, with the password only if `show_password` is set
+pub(crate) fn credential_to_json(
+    credential: &Credential,
+    show_password: bool,
+    pretty: Option<bool>,
+) -> Result<String, Error> {
+    to_json(&CredentialJson::new(credential, show_password), pretty)
+}
+
+/// Renders the credentials as a JSON array, with the passwords only if `show_password` is set
+pub(crate) fn credentials_to_json(
+    credentials: &[Credential],
+    show_password: bool,
+    pretty: Option<bool>,
+) -> Result<String, Error> {
+    let credentials: Vec<CredentialJson> = credentials
+        .iter()
+        .map(|c| CredentialJson::new(c, show_password))
+        .collect();
+    to_json(&credentials, pretty)
+}
+
+/// Renders the value as JSON. Without an explicit choice the output is indented when
+/// printed to a terminal and compact on one line when piped to another program.
+pub(crate) fn to_json<T: Serialize>(value: &T, pretty: Option<bool>) -> Result<String, Error> {
+    if pretty.unwrap_or_else(|| std::io::stdout().is_terminal()) {
+        Ok(serde_json::to_string_pretty(value)?)
+    } else {
+        Ok(serde_json::to_string(value)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::mixed_vault;
+    use crate::vault::vault_trait::PasswordVault;
+    use serde_json::Value;
+
+    fn credentials() -> Vec<Credential> {
+        vec![
+            Credential::new(None, "s3cret", "example.com", "john", None, &[], None),
+            Credential::new(None, "pw2", "other.org", "jane", None, &[], Some("a note")),
+        ]
+    }
+
+    #[test]
+    fn json_is_parseable_and_leaves_out_the_passwords() {
+        for pretty in [Some(true), Some(false)] {
+            let json = credentials_to_json(&credentials(), false, pretty).unwrap();
+            let parsed: Value = serde_json::from_str(&json).unwrap();
+            let items = parsed.as_array().unwrap();
+            assert_eq!(items.len(), 2);
+            assert_eq!(items[0]["service"], "example.com");
+            assert_eq!(items[1]["notes"], "a note");
+            assert!(items.iter().all(|item| item.get("password").is_none()));
+        }
+    }
+
+    #[test]
+    fn json_of_the_vault_credentials_has_no_notes_or_cards() {
+        let vault = mixed_vault("output-mixed");
+        let json = credentials_to_json(&vault.grep(None), true, Some(false)).unwrap();
+        let parsed: Value = serde_json::from_str(&json).unwrap();
+        assert_eq!(parsed.as_array().unwrap().len(), 1);
+        assert!(!json.contains("secret note body"), "{}", json);
+        assert!(!json.contains("(no service)"), "{}", json);
+    }
+
+    #[test]
+    fn only_a_missing_username_is_shown_with_a_dash() {
+        let blank = Credential::new(None, "pw", "blank.com", "", None, &[], None);
+        assert_eq!(username_cell(&blank).content(), "");
+        let api_key = blank.clone().with_no_username(true);
+        assert_eq!(username_cell(&api_key).content(), "—");
+    }
+
+    #[test]
+    fn json_has_the_password_when_asked() {
+        let json = credential_to_json(&credentials()[0], true, Some(false)).unwrap();
+        let parsed: Value = serde_json::from_str(&json).unwrap();
+        assert_eq!(parsed["password"], "s3cret");
+        assert_eq!(parsed["username"], "john");
+    }
+
+    #[test]
+    fn compact_json_is_on_one_line() {
+        let json = credentials_to_json(&credentials(), false, Some(false)).unwrap();
+        assert!(!json.contains('\n'));
+        let json = credentials_to_json(&credentials(), false, Some(true)).unwrap();
+        assert!(json.contains('\n'));
+    }
+}
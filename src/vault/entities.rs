@@ -8,6 +8,7 @@ use std::num::ParseIntError;
 use std::str::FromStr;
 use std::time::SystemTimeError;
 use uuid::Uuid;
+use zeroize::Zeroize;
 
 use crate::crypto::SPECIAL;
 
@@ -63,6 +64,48 @@ pub struct Credential {
     username: String,
     #[serde(default = "default_last_modified")]
     last_modified: DateTime<Utc>,
+    #[serde(default, with = "tag_list")]
+    tags: Vec<String>,
+    #[serde(default)]
+    notes: Option<String>,
+    // Set by the vault, the CSV files don't have it
+    #[serde(skip)]
+    created: Option<DateTime<Utc>>,
+    // Saved on purpose without a username, unlike an empty username left blank or imported
+    #[serde(skip)]
+    no_username: bool,
+}
+
+// The tags are kept in one comma separated column so that they fit in a CSV row
+mod tag_list {
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(tags: &[String], serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&tags.join(","))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Vec<String>, D::Error> {
+        Ok(super::parse_tags(&String::deserialize(deserializer)?))
+    }
+}
+
+/// Splits a comma separated list of tags, leaving out the empty ones
+pub fn parse_tags(value: &str) -> Vec<String> {
+    value
+        .split(',')
+        .map(|tag| sanitize(tag.trim()))
+        .filter(|tag| !tag.is_empty())
+        .collect()
+}
+
+// Scrub the password from memory when the credential is no longer used
+impl Drop for Credential {
+    fn drop(&mut self) {
+        self.password.zeroize();
+        self.notes.zeroize();
+    }
 }
 
 fn default_last_modified() -> DateTime<Utc> {
@@ -76,6 +119,8 @@ impl Credential {
         service: &str,
         username: &str,
         last_modified: Option<DateTime<Utc>>,
+        tags: &[String],
+        notes: Option<&str>,
     ) -> Self {
         Credential {
             uuid: uuid.map(|id| id.clone()).unwrap_or_else(|| Uuid::new_v4()),
@@ -83,9 +128,28 @@ impl Credential {
             service: sanitize(service),
             username: sanitize(username),
             last_modified: last_modified.unwrap_or(Utc::now()),
+            tags: tags.iter().map(|t| sanitize(t)).collect(),
+            notes: notes.map(sanitize).filter(|n| !n.trim().is_empty()),
+            created: None,
+            no_username: false,
         }
     }
 
+    /// The credential marked as having no username on purpose, e.g. an API key saved with `--no-username`
+    pub fn with_no_username(mut self, no_username: bool) -> Self {
+        if no_username {
+            self.username.clear();
+        }
+        self.no_username = no_username;
+        self
+    }
+
+    /// The credential with the time it was saved to the vault, None for entries that don't have it
+    pub fn with_created(mut self, created: Option<DateTime<Utc>>) -> Self {
+        self.created = created;
+        self
+    }
+
     pub fn uuid(&self) -> &Uuid {
         &self.uuid
     }
@@ -102,9 +166,121 @@ impl Credential {
         &self.username
     }
 
+    /// False for credentials saved intentionally without a username. An empty username that was
+    /// left blank or imported is still a username.
+    pub fn has_username(&self) -> bool {
+        !self.no_username
+    }
+
+    pub fn tags(&self) -> &[String] {
+        &self.tags
+    }
+
+    pub fn has_tag(&self, tag: &str) -> bool {
+        self.tags.iter().any(|t| t.eq_ignore_ascii_case(tag))
+    }
+
+    pub fn has_all_tags(&self, tags: &[String]) -> bool {
+        tags.iter().all(|tag| self.has_tag(tag))
+    }
+
+    pub fn last_modified(&self) -> &DateTime<Utc> {
+        &self.last_modified
+    }
+
+    pub fn created(&self) -> Option<&DateTime<Utc>> {
+        self.created.as_ref()
+    }
+
+    pub fn notes(&self) -> Option<&str> {
+        self.notes.as_deref()
+    }
+}
+
+#[derive(Clone)]
+pub struct RecoveryCodes {
+    id: Uuid,
+    service: String,
+    username: String,
+    codes: Vec<String>,
+    last_modified: DateTime<Utc>,
+}
+
+impl RecoveryCodes {
+    pub fn new(
+        id: Option<&Uuid>,
+        service: &str,
+        username: &str,
+        codes: &[String],
+        last_modified: Option<DateTime<Utc>>,
+    ) -> Self {
+        RecoveryCodes {
+            id: id.copied().unwrap_or_else(Uuid::new_v4),
+            service: sanitize(service),
+            username: sanitize(username),
+            codes: codes
+                .iter()
+                .map(|c| sanitize(c.trim()))
+                .filter(|c| !c.is_empty())
+                .collect(),
+            last_modified: last_modified.unwrap_or_else(Utc::now),
+        }
+    }
+
+    pub fn id(&self) -> &Uuid {
+        &self.id
+    }
+
+    pub fn service(&self) -> &str {
+        &self.service
+    }
+
+    pub fn username(&self) -> &str {
+        &self.username
+    }
+
+    pub fn codes(&self) -> &[String] {
+        &self.codes
+    }
+
     pub fn last_modified(&self) -> &DateTime<Utc> {
         &self.last_modified
     }
+
+    pub fn without_code(&self, index: usize) -> RecoveryCodes {
+        let mut codes = self.codes.clone();
+        codes.remove(index);
+        RecoveryCodes {
+            codes,
+            ..self.clone()
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum DiffStatus {
+    OnlyInVault,
+    OnlyInFile,
+    PasswordDiffers,
+}
+
+impl Display for DiffStatus {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DiffStatus::OnlyInVault => write!(f, "only in vault"),
+            DiffStatus::OnlyInFile => write!(f, "only in file"),
+            DiffStatus::PasswordDiffers => write!(f, "password differs"),
+        }
+    }
+}
+
+/// A difference between the vault and a file, for a credential identified by service and username
+#[derive(Clone, Serialize)]
+pub struct CredentialDiff {
+    pub service: String,
+    pub username: String,
+    pub status: DiffStatus,
 }
 
 #[derive(Clone)]
@@ -506,3 +682,38 @@ impl FromStr for Address {
         })
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tagged(tags: &[&str]) -> Credential {
+        let tags: Vec<String> = tags.iter().map(|t| t.to_string()).collect();
+        Credential::new(None, "secret", "example.com", "user", None, &tags, None)
+    }
+
+    fn tags(tags: &[&str]) -> Vec<String> {
+        tags.iter().map(|t| t.to_string()).collect()
+    }
+
+    #[test]
+    fn filtering_by_one_tag_ignores_case() {
+        let credential = tagged(&["work", "Banking"]);
+        assert!(credential.has_all_tags(&tags(&["banking"])));
+        assert!(!credential.has_all_tags(&tags(&["personal"])));
+    }
+
+    #[test]
+    fn filtering_by_many_tags_needs_all_of_them() {
+        let credential = tagged(&["work", "banking"]);
+        assert!(credential.has_all_tags(&tags(&["work", "banking"])));
+        assert!(!credential.has_all_tags(&tags(&["work", "personal"])));
+    }
+
+    #[test]
+    fn no_tags_match_everything() {
+        assert!(tagged(&[]).has_all_tags(&[]));
+        assert!(tagged(&["work"]).has_all_tags(&[]));
+        assert!(!tagged(&[]).has_all_tags(&tags(&["work"])));
+    }
+}
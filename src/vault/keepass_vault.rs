@@ -1,21 +1,35 @@
-use crate::vault::entities::{Address, Credential, Error, Expiry, Note, PaymentCard, Totp};
-use crate::vault::vault_trait::{NoteVault, PasswordVault, PaymentVault, TotpVault, Vault};
+use crate::store;
+use crate::vault::entities::{
+    Address, Credential, Error, Expiry, Note, PaymentCard, RecoveryCodes, Totp,
+};
+use crate::vault::vault_trait::{
+    NoteVault, PasswordVault, PaymentVault, RecoveryCodesVault, TotpVault, Vault,
+};
 use chrono::{DateTime, NaiveDateTime, Utc};
 use keepass_ng::db::{
     group_get_children, node_is_entry, node_is_group, search_node_by_uuid, Database, Entry, Group,
     Node, NodeIterator, NodePtr, SerializableNodePtr,
 };
-use keepass_ng::error::DatabaseSaveError;
+use keepass_ng::error::{DatabaseKeyError, DatabaseSaveError};
 use keepass_ng::{error::DatabaseOpenError, DatabaseConfig, DatabaseKey};
 
 use log::debug;
-use std::fs::{File, OpenOptions};
+use std::fs::{self, File};
 use std::path::Path;
 use std::str::FromStr;
 use uuid::Uuid;
+use zeroize::{Zeroize, Zeroizing};
+
+const RECOVERY_CODES_GROUP: &str = "Recovery codes";
+const PAYMENTS_GROUP: &str = "Payments";
+const NOTES_GROUP: &str = "Notes";
+// The KeePass tag of the entries saved without a username on purpose, not shown with the other tags
+const NO_USERNAME_TAG: &str = "passlane:no-username";
+// The KeePass default for the number of history items to keep
+const DEFAULT_HISTORY_MAX_ITEMS: usize = 10;
 
 pub struct KeepassVault {
-    password: String,
+    password: Zeroizing<String>,
     db: Database,
     filepath: String,
     keyfile: Option<String>,
@@ -57,19 +71,23 @@ fn node_has_totp(node: &NodePtr) -> bool {
 }
 
 impl KeepassVault {
-    pub fn open(
+    /// Opens the vault, returns `None` when the master password or the key file is incorrect.
+    pub fn verify_master_password(
         password: &str,
         filepath: &str,
         keyfile_path: Option<String>,
-    ) -> Result<KeepassVault, Error> {
+    ) -> Result<Option<KeepassVault>, Error> {
         debug!("Opening database '{}'", filepath);
-        let db = Self::open_database(filepath, password, &keyfile_path)?;
-        Ok(Self {
-            password: String::from(password),
-            db,
-            filepath: filepath.to_string(),
-            keyfile: keyfile_path,
-        })
+        match Self::open_database(filepath, password, &keyfile_path) {
+            Ok(db) => Ok(Some(Self {
+                password: Zeroizing::new(String::from(password)),
+                db,
+                filepath: filepath.to_string(),
+                keyfile: keyfile_path,
+            })),
+            Err(DatabaseOpenError::Key(DatabaseKeyError::IncorrectKey)) => Ok(None),
+            Err(e) => Err(e.into()),
+        }
     }
 
     pub fn new(
@@ -87,16 +105,91 @@ impl KeepassVault {
             let mut file = File::open(keyfile_path)?;
             key = key.with_keyfile(&mut file)?;
         }
-        db.save(&mut File::create(filepath)?, key)?;
+        db.save(&mut store::create_private_file(filepath)?, key)?;
 
         Ok(KeepassVault {
             db,
-            password: password.to_string(),
+            password: Zeroizing::new(password.to_string()),
             filepath: filepath.to_string(),
             keyfile: keyfile.map(ToString::to_string),
         })
     }
 
+    /// Maps the entries of a KeePass file made by another app to credentials for importing. The service
+    /// is the URL of the entry, or the group titles and the entry title when there is no URL. The entries
+    /// that can't be imported are errors with the reason.
+    pub fn import_credentials(&self) -> Vec<Result<Credential, String>> {
+        let recycle_bin = self.db.get_recycle_bin().map(|bin| bin.borrow().get_uuid());
+        let mut credentials = Vec::new();
+        Self::collect_import_credentials(
+            &self.get_root(),
+            &mut Vec::new(),
+            recycle_bin,
+            &mut credentials,
+        );
+        credentials
+    }
+
+    fn collect_import_credentials(
+        group: &NodePtr,
+        group_titles: &mut Vec<String>,
+        recycle_bin: Option<Uuid>,
+        credentials: &mut Vec<Result<Credential, String>>,
+    ) {
+        for child in group_get_children(group).unwrap_or_default() {
+            if node_is_group(&child) {
+                if Some(child.borrow().get_uuid()) == recycle_bin {
+                    continue;
+                }
+                group_titles.push(child.borrow().get_title().unwrap_or("").to_string());
+                Self::collect_import_credentials(&child, group_titles, recycle_bin, credentials);
+                group_titles.pop();
+            } else if let Some(entry) = child.borrow().as_any().downcast_ref::<Entry>() {
+                credentials.push(Self::entry_to_import_credential(entry, group_titles));
+            }
+        }
+    }
+
+    fn entry_to_import_credential(
+        e: &Entry,
+        group_titles: &[String],
+    ) -> Result<Credential, String> {
+        let title = e.get_title().unwrap_or("").trim();
+        let service = match e.get_url().map(str::trim).filter(|url| !url.is_empty()) {
+            Some(url) => url.to_string(),
+            None if !title.is_empty() => group_titles
+                .iter()
+                .map(String::as_str)
+                .chain([title])
+                .collect::<Vec<&str>>()
+                .join("/"),
+            None => return Err("entry without a title: no URL or title".to_string()),
+        };
+        let password = e
+            .get_password()
+            .filter(|password| !password.is_empty())
+            .ok_or(format!("entry '{}': no password", title))?;
+        let no_username = e.get_tags().iter().any(|tag| tag == NO_USERNAME_TAG);
+        let tags: Vec<String> = e
+            .get_tags()
+            .iter()
+            .filter(|tag| *tag != NO_USERNAME_TAG)
+            .cloned()
+            .collect();
+        Ok(Credential::new(
+            None,
+            password,
+            &service,
+            e.get_username().unwrap_or(""),
+            e.get_times()
+                .get_last_modification()
+                .map(|dt| DateTime::<Utc>::from_naive_utc_and_offset(dt, Utc)),
+            &tags,
+            e.get_notes(),
+        )
+        .with_no_username(no_username))
+    }
+
     fn get_root(&self) -> SerializableNodePtr {
         self.db.root.clone()
     }
@@ -105,26 +198,45 @@ impl KeepassVault {
         self.get_root().borrow().get_uuid()
     }
 
-    fn save_database(&self) -> Result<(), DatabaseSaveError> {
-        let mut file = OpenOptions::new()
-            .read(true)
-            .write(true)
-            .create_new(!Path::new(&self.filepath).exists())
-            .open(&self.filepath)
-            .unwrap();
-
-        let (_, key) =
-            Self::get_database_key(&self.filepath, &self.password, &self.keyfile).unwrap();
-        debug!("Saving database to file '{}'", &self.filepath);
-
-        self.db.save(&mut file, key)
+    // Writes to a temporary file that is then renamed over the vault file, so that a failed
+    // or interrupted save leaves the previous vault file intact. The temporary file is readable
+    // only by the user, and named uniquely so that processes saving at the same time don't
+    // write to the same file.
+    fn save_database(&self) -> Result<(), Error> {
+        let temp_path = format!(
+            "{}.{}-{:08x}.tmp",
+            self.filepath,
+            std::process::id(),
+            rand::random::<u32>()
+        );
+        let key = Self::database_key(&self.password, &self.keyfile);
+        debug!("Saving database to file '{}'", &temp_path);
+
+        let written = store::create_private_file(&temp_path).and_then(|mut file| {
+            self.db.save(&mut file, key)?;
+            file.sync_all()?;
+            Ok(())
+        });
+        if let Err(e) = written {
+            let _ = fs::remove_file(&temp_path);
+            return Err(e);
+        }
+        if let Ok(metadata) = fs::metadata(&self.filepath) {
+            fs::set_permissions(&temp_path, metadata.permissions())?;
+        }
+        debug!(
+            "Replacing file '{}' with the saved database",
+            &self.filepath
+        );
+        fs::rename(&temp_path, &self.filepath)?;
+        Ok(())
     }
 
     fn open_database(
         filepath: &str,
         password: &str,
         keyfile: &Option<String>,
-    ) -> Result<Database, Error> {
+    ) -> Result<Database, DatabaseOpenError> {
         if !Path::new(filepath).exists() {
             debug!(
                 "Database file '{}' does not exist, creating new database",
@@ -159,7 +271,11 @@ impl KeepassVault {
         keyfile: &Option<String>,
     ) -> Result<(File, DatabaseKey), DatabaseOpenError> {
         let db_file = File::open(filepath)?;
-        let key = match keyfile {
+        Ok((db_file, Self::database_key(password, keyfile)))
+    }
+
+    fn database_key(password: &str, keyfile: &Option<String>) -> DatabaseKey {
+        match keyfile {
             Some(kf) => {
                 debug!("Using keyfile '{}' and password", kf);
                 let file = &mut File::open(kf).expect("Failed to open keyfile");
@@ -169,13 +285,22 @@ impl KeepassVault {
                     .unwrap()
             }
             None => DatabaseKey::new().with_password(password),
-        };
-        Ok((db_file, key))
+        }
     }
 
     fn load_credentials(&self, grep: Option<&str>) -> Vec<Credential> {
+        // the cards, notes and recovery codes are entries too but not credentials
+        let other_groups: Vec<Uuid> = [PAYMENTS_GROUP, NOTES_GROUP, RECOVERY_CODES_GROUP]
+            .iter()
+            .filter_map(|name| self.find_group(name))
+            .collect();
         NodeIterator::new(&self.get_root())
             .filter(node_is_entry)
+            .filter(|node| {
+                node.borrow()
+                    .get_parent()
+                    .is_none_or(|parent| !other_groups.contains(&parent))
+            })
             .map(Self::node_to_credential)
             .filter(|cred| {
                 if let Some(grep) = &grep {
@@ -213,7 +338,10 @@ impl KeepassVault {
     }
 
     fn load_payments(&self) -> Vec<PaymentCard> {
-        let payments_group_uuid = self.find_group("Payments").unwrap();
+        // the group is created when the first card is saved
+        let Some(payments_group_uuid) = self.find_group(PAYMENTS_GROUP) else {
+            return vec![];
+        };
         let payments_group = search_node_by_uuid(&self.get_root(), payments_group_uuid).unwrap();
         NodeIterator::new(&payments_group)
             .filter(node_is_entry)
@@ -222,7 +350,9 @@ impl KeepassVault {
     }
 
     fn load_notes(&self) -> Vec<Note> {
-        let payments_group_uuid = self.find_group("Notes").unwrap();
+        let Some(payments_group_uuid) = self.find_group(NOTES_GROUP) else {
+            return vec![];
+        };
         let payments_group = search_node_by_uuid(&self.get_root(), payments_group_uuid).unwrap();
         NodeIterator::new(&payments_group)
             .filter(node_is_entry)
@@ -230,15 +360,51 @@ impl KeepassVault {
             .collect()
     }
 
+    fn load_recovery_codes(&self) -> Vec<RecoveryCodes> {
+        let Some(group_uuid) = self.find_group(RECOVERY_CODES_GROUP) else {
+            return vec![];
+        };
+        let group = search_node_by_uuid(&self.get_root(), group_uuid).unwrap();
+        NodeIterator::new(&group)
+            .filter(node_is_entry)
+            .map(Self::node_to_recovery_codes)
+            .collect()
+    }
+
     fn node_to_credential(node: NodePtr) -> Credential {
-        let (username, service, password, uuid, modified_date_time) = Self::get_node_values(node);
+        let node = node.borrow();
+        Self::entry_to_credential(node.as_any().downcast_ref::<Entry>().unwrap())
+    }
+
+    fn entry_to_credential(e: &Entry) -> Credential {
+        let (username, service, password, uuid, modified_date_time, mut tags, notes) =
+            Self::get_entry_values(e);
+        let no_username = tags.iter().any(|tag| tag == NO_USERNAME_TAG);
+        tags.retain(|tag| tag != NO_USERNAME_TAG);
         Credential::new(
             Some(&uuid),
             &password,
             &service,
             &username,
             modified_date_time.map(|dt| DateTime::<Utc>::from_naive_utc_and_offset(dt, Utc)),
+            &tags,
+            notes.as_deref(),
+        )
+        .with_created(
+            e.get_times()
+                .get_creation()
+                .map(|dt| DateTime::<Utc>::from_naive_utc_and_offset(dt, Utc)),
         )
+        .with_no_username(no_username)
+    }
+
+    // The tags of the credential and the marker of a missing username
+    fn entry_tags(credential: &Credential) -> Vec<String> {
+        let mut tags = credential.tags().to_vec();
+        if !credential.has_username() {
+            tags.push(NO_USERNAME_TAG.to_string());
+        }
+        tags
     }
 
     fn node_to_totp(node: NodePtr) -> Totp {
@@ -265,10 +431,18 @@ impl KeepassVault {
         }
     }
 
-    fn get_node_values(node: NodePtr) -> (String, String, String, Uuid, Option<NaiveDateTime>) {
-        let node = node.borrow();
-        let e = node.as_any().downcast_ref::<Entry>().unwrap();
-        let username = e.get_username().unwrap_or("(no username)");
+    fn get_entry_values(
+        e: &Entry,
+    ) -> (
+        String,
+        String,
+        String,
+        Uuid,
+        Option<NaiveDateTime>,
+        Vec<String>,
+        Option<String>,
+    ) {
+        let username = e.get_username().unwrap_or_default();
         let service = e.get_url().unwrap_or("(no service)");
         let password = e.get_password().unwrap_or("(no password)");
         let uuid = e.get_uuid();
@@ -279,6 +453,28 @@ impl KeepassVault {
             password.to_string(),
             uuid,
             last_modified,
+            e.get_tags().clone(),
+            e.get_notes().map(String::from),
+        )
+    }
+
+    fn node_to_recovery_codes(node: NodePtr) -> RecoveryCodes {
+        let node = node.borrow();
+        let e = node.as_any().downcast_ref::<Entry>().unwrap();
+        let codes: Vec<String> = e
+            .get_password()
+            .unwrap_or("")
+            .lines()
+            .map(String::from)
+            .collect();
+        RecoveryCodes::new(
+            Some(&e.get_uuid()),
+            e.get_title().unwrap_or("(no service)"),
+            e.get_username().unwrap_or("(no username)"),
+            &codes,
+            e.get_times()
+                .get_last_modification()
+                .map(|dt| DateTime::<Utc>::from_naive_utc_and_offset(dt, Utc)),
         )
     }
 
@@ -460,6 +656,8 @@ impl KeepassVault {
                         entry.set_username(Some(credentials.username()));
                         entry.set_password(Some(credentials.password()));
                         entry.set_url(Some(&credentials.service()));
+                        entry.set_notes(credentials.notes());
+                        *entry.get_tags_mut() = Self::entry_tags(credentials);
                         entry.get_uuid()
                     })
             })
@@ -523,6 +721,25 @@ impl KeepassVault {
             })
     }
 
+    fn create_recovery_codes_entry(
+        &mut self,
+        parent_uuid: &Uuid,
+        codes: &RecoveryCodes,
+    ) -> keepass_ng::Result<Option<Uuid>> {
+        self.db.create_new_entry(*parent_uuid, 0).map(|node| {
+            node.borrow_mut()
+                .as_any_mut()
+                .downcast_mut::<Entry>()
+                .map(|entry| {
+                    entry.set_title(Some(codes.service()));
+                    entry.set_username(Some(codes.username()));
+                    // the codes are kept in the protected password field so that they are encrypted like passwords
+                    entry.set_password(Some(&codes.codes().join("\n")));
+                    entry.get_uuid()
+                })
+        })
+    }
+
     fn do_delete(&mut self, uuid: &Uuid, save: bool) -> Result<(), Error> {
         debug!("Deleting with uuid '{}'", uuid);
         self.db.remove_node_by_uuid(*uuid)?;
@@ -546,6 +763,8 @@ impl KeepassVault {
             {
                 let mut node = node_ref.borrow_mut();
                 if let Some(entry) = node.as_any_mut().downcast_mut::<Entry>() {
+                    // commit the current values first so that they are kept in the history
+                    entry.update_history();
                     update_fn(entry);
                     entry.update_history();
                 } else {
@@ -566,20 +785,20 @@ impl KeepassVault {
 
 impl PasswordVault for KeepassVault {
     fn get_master_password(&self) -> String {
-        self.password.clone()
+        self.password.to_string()
     }
 
     fn grep(&self, grep: Option<&str>) -> Vec<Credential> {
         self.load_credentials(grep)
     }
 
-    fn save_credentials(&mut self, credentials: &Vec<Credential>) -> Result<i8, Error> {
+    fn save_credentials(&mut self, credentials: &Vec<Credential>) -> Result<usize, Error> {
         let group = self.find_or_create_group("Passwords");
         for c in credentials {
             self.create_password_entry(&group, c)?;
         }
         self.save_database()?;
-        Ok(credentials.len() as i8)
+        Ok(credentials.len())
     }
 
     fn save_one_credential(&mut self, credentials: Credential) -> Result<(), Error> {
@@ -594,32 +813,52 @@ impl PasswordVault for KeepassVault {
             entry.set_username(Some(credential.username()));
             entry.set_password(Some(credential.password()));
             entry.set_url(Some(credential.service()));
+            entry.set_notes(credential.notes());
+            *entry.get_tags_mut() = Self::entry_tags(&credential);
         })
     }
 
+    fn password_history(&self, uuid: &Uuid) -> Vec<Credential> {
+        let Some(node) = self.db.search_node_by_uuid(*uuid) else {
+            return vec![];
+        };
+        let node = node.borrow();
+        let Some(entry) = node.as_any().downcast_ref::<Entry>() else {
+            return vec![];
+        };
+        let max_items = self
+            .db
+            .meta
+            .history_max_items
+            .unwrap_or(DEFAULT_HISTORY_MAX_ITEMS);
+        // the history has a version for every change, keep the ones where the password changed
+        let mut newer_password = entry.get_password().unwrap_or("").to_string();
+        let mut versions = Vec::new();
+        for version in entry.get_history().iter().flat_map(|h| h.get_entries()) {
+            let password = version.get_password().unwrap_or("");
+            if password != newer_password {
+                newer_password = password.to_string();
+                versions.push(Self::entry_to_credential(version));
+            }
+        }
+        newer_password.zeroize();
+        versions.truncate(max_items);
+        versions
+    }
+
     fn delete_credentials(&mut self, uuid: &Uuid) -> Result<(), Error> {
         self.do_delete(uuid, true)?;
         Ok(())
     }
 
-    fn delete_matching(&mut self, grep: &str) -> Result<i8, Error> {
-        let root = self.get_root();
-        let matching: Vec<NodePtr> = NodeIterator::new(&root)
-            .filter(node_is_entry)
-            .filter(|node| {
-                let node = node.borrow();
-                let e = node.as_any().downcast_ref::<Entry>().unwrap();
-                let username = e.get_username().unwrap_or("(no username)");
-                let service = e.get_url().unwrap_or("(no service)");
-                username.contains(grep) || service.contains(grep)
-            })
-            .collect();
-        // delete
-        for node in &matching {
-            self.do_delete(&node.borrow().get_uuid(), false)?;
+    fn delete_matching(&mut self, grep: &str) -> Result<usize, Error> {
+        // match the same way as grep so that exactly the listed credentials are deleted
+        let matching = self.load_credentials(Some(grep));
+        for cred in &matching {
+            self.do_delete(cred.uuid(), false)?;
         }
         self.save_database()?;
-        Ok(matching.len() as i8)
+        Ok(matching.len())
     }
 }
 
@@ -629,7 +868,7 @@ impl PaymentVault for KeepassVault {
     }
 
     fn save_payment(&mut self, payment: PaymentCard) -> Result<(), Error> {
-        let group = self.find_or_create_group("Payments");
+        let group = self.find_or_create_group(PAYMENTS_GROUP);
         self.create_payment_entry(&group, &payment)
             .expect("Failed to save payment");
         self.save_database()?;
@@ -670,7 +909,7 @@ impl NoteVault for KeepassVault {
     }
 
     fn save_note(&mut self, note: &Note) -> Result<(), Error> {
-        let group = self.find_or_create_group("Notes");
+        let group = self.find_or_create_group(NOTES_GROUP);
         self.create_note_entry(&group, &note)
             .expect("Failed to save note");
         self.save_database()?;
@@ -716,4 +955,106 @@ impl TotpVault for KeepassVault {
     }
 }
 
+impl RecoveryCodesVault for KeepassVault {
+    fn find_recovery_codes(&self, grep: Option<&str>) -> Vec<RecoveryCodes> {
+        self.load_recovery_codes()
+            .into_iter()
+            .filter(|codes| {
+                if let Some(grep) = &grep {
+                    if !codes
+                        .service()
+                        .to_lowercase()
+                        .contains(&grep.to_lowercase())
+                        && !codes
+                            .username()
+                            .to_lowercase()
+                            .contains(&grep.to_lowercase())
+                    {
+                        return false;
+                    }
+                }
+                true
+            })
+            .collect()
+    }
+
+    fn save_recovery_codes(&mut self, codes: &RecoveryCodes) -> Result<(), Error> {
+        let group = self.find_or_create_group(RECOVERY_CODES_GROUP);
+        self.create_recovery_codes_entry(&group, codes)?;
+        self.save_database()?;
+        Ok(())
+    }
+
+    fn update_recovery_codes(&mut self, codes: RecoveryCodes) -> Result<(), Error> {
+        let uuid = codes.id();
+        self.update_entry(*uuid, |entry| {
+            entry.set_title(Some(codes.service()));
+            entry.set_username(Some(codes.username()));
+            entry.set_password(Some(&codes.codes().join("\n")));
+        })
+    }
+}
+
 impl Vault for KeepassVault {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::test_dir;
+
+    fn find<'a>(credentials: &'a [Credential], service: &str) -> &'a Credential {
+        credentials.iter().find(|c| c.service() == service).unwrap()
+    }
+
+    #[test]
+    fn no_username_is_kept_apart_from_an_empty_one() {
+        let path = test_dir("vault-no-username").join("store.kdbx");
+        let path = path.to_string_lossy().to_string();
+        let mut vault = KeepassVault::new(&path, "test", None).unwrap();
+        let tags = ["api".to_string()];
+        let api_key = Credential::new(None, "key", "api.com", "", None, &tags, None);
+        vault
+            .save_one_credential(api_key.with_no_username(true))
+            .unwrap();
+        let blank = Credential::new(None, "pw", "blank.com", "", None, &[], None);
+        vault.save_one_credential(blank).unwrap();
+
+        let vault = KeepassVault::verify_master_password("test", &path, None)
+            .unwrap()
+            .unwrap();
+        let credentials = vault.grep(None);
+        let api_key = find(&credentials, "api.com");
+        assert!(!api_key.has_username());
+        assert_eq!(api_key.username(), "");
+        assert_eq!(api_key.tags(), ["api"]);
+        let blank = find(&credentials, "blank.com");
+        assert!(blank.has_username());
+        assert_eq!(blank.username(), "");
+    }
+
+    #[test]
+    fn update_keeps_the_missing_username() {
+        let path = test_dir("vault-no-username-update").join("store.kdbx");
+        let mut vault = KeepassVault::new(&path.to_string_lossy(), "test", None).unwrap();
+        let api_key = Credential::new(None, "key", "api.com", "", None, &[], None);
+        vault
+            .save_one_credential(api_key.with_no_username(true))
+            .unwrap();
+        let saved = vault.grep(None).remove(0);
+        let rotated = Credential::new(
+            Some(saved.uuid()),
+            "new key",
+            "api.com",
+            "",
+            None,
+            &[],
+            None,
+        )
+        .with_no_username(!saved.has_username());
+        vault.update_credential(rotated).unwrap();
+        let updated = vault.grep(None).remove(0);
+        assert_eq!(updated.password(), "new key");
+        assert!(!updated.has_username());
+        assert!(updated.tags().is_empty());
+    }
+}
@@ -1,4 +1,4 @@
-use crate::vault::entities::{Credential, Error, Note, PaymentCard, Totp};
+use crate::vault::entities::{Credential, Error, Note, PaymentCard, RecoveryCodes, Totp};
 use uuid::Uuid;
 
 pub trait PasswordVault {
@@ -6,15 +6,18 @@ pub trait PasswordVault {
 
     fn grep(&self, grep: Option<&str>) -> Vec<Credential>;
 
-    fn save_credentials(&mut self, credentials: &Vec<Credential>) -> Result<i8, Error>;
+    fn save_credentials(&mut self, credentials: &Vec<Credential>) -> Result<usize, Error>;
 
     fn save_one_credential(&mut self, credential: Credential) -> Result<(), Error>;
 
     fn update_credential(&mut self, credential: Credential) -> Result<(), Error>;
 
+    /// The earlier passwords of the credential, the newest first
+    fn password_history(&self, uuid: &Uuid) -> Vec<Credential>;
+
     fn delete_credentials(&mut self, uuid: &Uuid) -> Result<(), Error>;
 
-    fn delete_matching(&mut self, grep: &str) -> Result<i8, Error>;
+    fn delete_matching(&mut self, grep: &str) -> Result<usize, Error>;
 }
 
 pub trait PaymentVault {
@@ -47,4 +50,12 @@ pub trait TotpVault {
     fn update_totp(&mut self, totp: Totp) -> Result<(), Error>;
 }
 
-pub trait Vault: PasswordVault + PaymentVault + NoteVault + TotpVault {}
+pub trait RecoveryCodesVault {
+    fn find_recovery_codes(&self, grep: Option<&str>) -> Vec<RecoveryCodes>;
+
+    fn save_recovery_codes(&mut self, codes: &RecoveryCodes) -> Result<(), Error>;
+
+    fn update_recovery_codes(&mut self, codes: RecoveryCodes) -> Result<(), Error>;
+}
+
+pub trait Vault: PasswordVault + PaymentVault + NoteVault + TotpVault + RecoveryCodesVault {}
@@ -0,0 +1,267 @@
+use sha1::{Digest, Sha1};
+use std::fs;
+use std::io::{BufRead, BufReader, Write};
+use std::net::TcpListener;
+use std::path::PathBuf;
+use std::process::{Command, Output, Stdio};
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+const MASTER_PASSWORD: &str = "correct horse";
+
+// Runs passlane with its own files in a temporary directory
+struct Passlane {
+    home: PathBuf,
+}
+
+impl Passlane {
+    fn new(name: &str) -> Passlane {
+        let home =
+            std::env::temp_dir().join(format!("passlane-cli-test-{}-{}", std::process::id(), name));
+        let _ = fs::remove_dir_all(&home);
+        fs::create_dir_all(&home).unwrap();
+        Passlane { home }
+    }
+
+    fn command(&self, args: &[&str]) -> Command {
+        let mut command = Command::new(env!("CARGO_BIN_EXE_passlane"));
+        command
+            .args(args)
+            .env("HOME", &self.home)
+            .env("PASSLANE_HOME", &self.home)
+            .env("PASSLANE_MASTER_PASSWORD", MASTER_PASSWORD)
+            .env_remove("RUST_LOG");
+        command
+    }
+
+    fn run(&self, args: &[&str]) -> Output {
+        self.run_with_stdin(args, "")
+    }
+
+    fn run_with_stdin(&self, args: &[&str], stdin: &str) -> Output {
+        output_with_stdin(self.command(args), stdin)
+    }
+
+    fn add(&self, service: &str, username: &str, password: &str) {
+        let output = self.run(&[
+            "add",
+            "--service",
+            service,
+            "--username",
+            username,
+            "--password",
+            password,
+        ]);
+        assert!(output.status.success(), "{}", stderr(&output));
+    }
+}
+
+impl Drop for Passlane {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.home);
+    }
+}
+
+fn output_with_stdin(mut command: Command, stdin: &str) -> Output {
+    let mut child = command
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(stdin.as_bytes())
+        .unwrap();
+    child.wait_with_output().unwrap()
+}
+
+fn stdout(output: &Output) -> String {
+    String::from_utf8_lossy(&output.stdout).to_string()
+}
+
+fn stderr(output: &Output) -> String {
+    String::from_utf8_lossy(&output.stderr).to_string()
+}
+
+#[test]
+fn found_credentials_exit_with_zero() {
+    let passlane = Passlane::new("found");
+    passlane.add("example.com", "john", "s3cret-Password1");
+    let output = passlane.run(&["show", "example", "--json"]);
+    assert_eq!(output.status.code(), Some(0), "{}", stderr(&output));
+    assert!(stdout(&output).contains("\"username\":\"john\""));
+}
+
+#[test]
+fn empty_search_exits_with_two_with_fail_on_empty() {
+    let passlane = Passlane::new("empty");
+    passlane.add("example.com", "john", "s3cret-Password1");
+    let output = passlane.run(&["show", "nothing", "--json"]);
+    assert_eq!(output.status.code(), Some(0));
+    let output = passlane.run(&["--fail-on-empty", "show", "nothing", "--json"]);
+    assert_eq!(output.status.code(), Some(2));
+}
+
+#[test]
+fn exact_service_exits_with_two_or_three() {
+    let passlane = Passlane::new("exact");
+    passlane.add("example.com", "john", "s3cret-Password1");
+    passlane.add("example.com", "jane", "s3cret-Password2");
+    let output = passlane.run(&["show", "--exact-service", "https://other.org", "--json"]);
+    assert_eq!(output.status.code(), Some(2));
+    let output = passlane.run(&["show", "--exact-service", "https://example.com", "--json"]);
+    assert_eq!(output.status.code(), Some(3));
+    let output = passlane.run(&[
+        "show",
+        "--exact-service",
+        "https://example.com",
+        "--username",
+        "jane",
+        "--json",
+    ]);
+    assert_eq!(output.status.code(), Some(0), "{}", stderr(&output));
+}
+
+#[test]
+fn wrong_master_password_exits_with_one() {
+    let passlane = Passlane::new("wrong-password");
+    passlane.add("example.com", "john", "s3cret-Password1");
+    let output = passlane
+        .command(&["show", "example", "--json"])
+        .env("PASSLANE_MASTER_PASSWORD", "wrong")
+        .stdin(Stdio::null())
+        .output()
+        .unwrap();
+    assert_eq!(output.status.code(), Some(1));
+    assert!(stderr(&output).contains("Wrong master password"));
+}
+
+// A range API that answers every prefix with the suffix of the breached password, and records the paths asked
+fn mock_range_api(breached_password: &str) -> (String, Arc<Mutex<Vec<String>>>) {
+    let hash = hex::encode_upper(Sha1::digest(breached_password.as_bytes()));
+    let body = format!(
+        "{}:42\r\n0000000000000000000000000000000000A:0\r\n",
+        &hash[5..]
+    );
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!("http://{}", listener.local_addr().unwrap());
+    let paths = Arc::new(Mutex::new(Vec::new()));
+    let recorded = paths.clone();
+    thread::spawn(move || {
+        for stream in listener.incoming() {
+            let mut stream = stream.unwrap();
+            let mut request_line = String::new();
+            BufReader::new(&stream)
+                .read_line(&mut request_line)
+                .unwrap();
+            let path = request_line.split_whitespace().nth(1).unwrap_or_default();
+            recorded.lock().unwrap().push(path.to_string());
+            write!(
+                stream,
+                "HTTP/1.1 200 OK\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                body.len(),
+                body
+            )
+            .unwrap();
+        }
+    });
+    (url, paths)
+}
+
+#[test]
+fn check_sends_only_the_hash_prefix_and_shows_no_progress_when_piped() {
+    let passlane = Passlane::new("check");
+    passlane.add("example.com", "john", "s3cret-Password1");
+    passlane.add("other.org", "jane", "Another-Passw0rd");
+    let (url, paths) = mock_range_api("s3cret-Password1");
+    let output = passlane
+        .command(&["check"])
+        .env("PASSLANE_HIBP_URL", &url)
+        .env("NO_PROXY", "127.0.0.1")
+        .stdin(Stdio::null())
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(stdout(&output).contains("1 of the 2 passwords were found in breaches"));
+    assert!(stdout(&output).contains("https://example.com"));
+    assert!(!stderr(&output).contains("Checked"));
+    let paths = paths.lock().unwrap();
+    assert_eq!(paths.len(), 2);
+    assert!(paths.iter().all(|path| path.len() == "/".len() + 5));
+}
+
+#[test]
+fn piped_generate_prints_only_the_password() {
+    let passlane = Passlane::new("generate-piped");
+    let output = passlane.run(&["generate", "--length", "24"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    let printed = stdout(&output);
+    assert_eq!(printed.lines().count(), 1, "{}", printed);
+    assert_eq!(printed.trim_end().chars().count(), 24, "{}", printed);
+}
+
+#[test]
+fn piped_password_is_saved_as_it_is() {
+    let passlane = Passlane::new("add-piped");
+    passlane.add("example.com", "john", "s3cret-Password1");
+    // too short for the password policy, which applies only to the clipboard
+    let output = passlane.run_with_stdin(
+        &["add", "--service", "example.org", "--username", "jane"],
+        "pin4\n",
+    );
+    assert!(output.status.success(), "{}", stderr(&output));
+    let output = passlane.run(&["show", "example.org", "--json", "--show-password"]);
+    assert_eq!(output.status.code(), Some(0), "{}", stderr(&output));
+    let shown = stdout(&output);
+    assert!(shown.contains("\"username\":\"jane\""), "{}", shown);
+    assert!(shown.contains("\"password\":\"pin4\""), "{}", shown);
+}
+
+#[test]
+fn verbose_log_prints_debug_records_to_stderr() {
+    let passlane = Passlane::new("verbose-log");
+    passlane.add("example.com", "john", "s3cret-Password1");
+    let output = passlane.run(&["-LL", "show", "example", "--json"]);
+    assert_eq!(output.status.code(), Some(0), "{}", stderr(&output));
+    assert!(stderr(&output).contains(" DEBUG "), "{}", stderr(&output));
+    assert!(!stdout(&output).contains("DEBUG"));
+    let output = passlane.run(&["-L", "show", "example", "--json"]);
+    assert!(!stderr(&output).contains(" DEBUG "), "{}", stderr(&output));
+}
+
+#[test]
+fn rotate_all_needs_yes_without_a_terminal() {
+    let passlane = Passlane::new("rotate-piped");
+    passlane.add("example.com", "john", "password");
+    let output = passlane.run_with_stdin(&["rotate-all", "--weaker-than", "fair"], "yes\n");
+    assert_eq!(output.status.code(), Some(1), "{}", stderr(&output));
+    assert!(stderr(&output).contains("use --yes"), "{}", stderr(&output));
+    let output = passlane.run(&["rotate-all", "--weaker-than", "fair", "--yes"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(stdout(&output).contains("Rotated 1 passwords, 0 failed"));
+    let output = passlane.run(&["rotate-all", "--weaker-than", "fair", "--yes"]);
+    assert!(stdout(&output).contains("No credentials weaker than fair found"));
+}
+
+#[test]
+fn import_asks_a_wrong_master_password_again() {
+    let passlane = Passlane::new("import-retries");
+    passlane.add("example.com", "john", "s3cret-Password1");
+    let file = passlane.home.join("import.csv");
+    fs::write(&file, "password,service,username\npw1,other.org,jane\n").unwrap();
+    let file = file.to_string_lossy().to_string();
+    let import = |stdin: &str| {
+        let mut command = passlane.command(&["csv", &file]);
+        command.env("PASSLANE_MASTER_PASSWORD", "wrong");
+        output_with_stdin(command, stdin)
+    };
+    let output = import("still wrong\nagain wrong\n");
+    assert_eq!(output.status.code(), Some(1));
+    assert!(stderr(&output).contains("Wrong master password 3 times, giving up"));
+    let output = import(&format!("still wrong\n{}\n", MASTER_PASSWORD));
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(stdout(&output).contains("Imported 1 new entries"));
+}